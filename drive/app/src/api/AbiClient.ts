@@ -7,13 +7,243 @@ import {
 
 // Generated types
 
+export type AppErrorPayload =
+  | { name: 'NotFound'; payload: AppError_NotFound }
+  | { name: 'Empty'; payload: AppError_Empty }
+  | { name: 'InvalidPattern'; payload: AppError_InvalidPattern }
+  | { name: 'InvalidMime'; payload: AppError_InvalidMime }
+  | { name: 'Forbidden'; payload: AppError_Forbidden }
+  | { name: 'NotContextOwner'; payload: AppError_NotContextOwner }
+  | { name: 'NotCaller'; payload: AppError_NotCaller }
+  | { name: 'Leased'; payload: AppError_Leased }
+  | { name: 'NothingToUndo' }
+  | { name: 'NothingToRedo' }
+  | { name: 'LastOwner'; payload: AppError_LastOwner }
+  | { name: 'MissingExpectedVersion'; payload: AppError_MissingExpectedVersion }
+  | { name: 'BlobNotReferenced'; payload: AppError_BlobNotReferenced }
+  | { name: 'InvalidQuota'; payload: AppError_InvalidQuota }
+  | { name: 'InvalidTransition'; payload: AppError_InvalidTransition }
+  | { name: 'NotPublished'; payload: AppError_NotPublished }
+  | { name: 'ExpiryBeforePublish'; payload: AppError_ExpiryBeforePublish }
+  | { name: 'Conflict'; payload: AppError_Conflict }
+  | { name: 'VersionConflict'; payload: AppError_VersionConflict }
+  | { name: 'OutOfRange'; payload: AppError_OutOfRange }
+  | { name: 'Quota'; payload: AppError_Quota }
+  | { name: 'InvalidOp'; payload: AppError_InvalidOp }
+  | { name: 'BatchOutOfOrder'; payload: AppError_BatchOutOfOrder }
+  | { name: 'BatchExpired'; payload: AppError_BatchExpired }
+  | { name: 'Storage'; payload: AppError_Storage }
+
+export const AppError = {
+  NotFound: (notFound: AppError_NotFound): AppErrorPayload => ({ name: 'NotFound', payload: notFound }),
+  Empty: (empty: AppError_Empty): AppErrorPayload => ({ name: 'Empty', payload: empty }),
+  InvalidPattern: (invalidPattern: AppError_InvalidPattern): AppErrorPayload => ({ name: 'InvalidPattern', payload: invalidPattern }),
+  InvalidMime: (invalidMime: AppError_InvalidMime): AppErrorPayload => ({ name: 'InvalidMime', payload: invalidMime }),
+  Forbidden: (forbidden: AppError_Forbidden): AppErrorPayload => ({ name: 'Forbidden', payload: forbidden }),
+  NotContextOwner: (notContextOwner: AppError_NotContextOwner): AppErrorPayload => ({ name: 'NotContextOwner', payload: notContextOwner }),
+  NotCaller: (notCaller: AppError_NotCaller): AppErrorPayload => ({ name: 'NotCaller', payload: notCaller }),
+  Leased: (leased: AppError_Leased): AppErrorPayload => ({ name: 'Leased', payload: leased }),
+  NothingToUndo: (): AppErrorPayload => ({ name: 'NothingToUndo' }),
+  NothingToRedo: (): AppErrorPayload => ({ name: 'NothingToRedo' }),
+  LastOwner: (lastOwner: AppError_LastOwner): AppErrorPayload => ({ name: 'LastOwner', payload: lastOwner }),
+  MissingExpectedVersion: (missingExpectedVersion: AppError_MissingExpectedVersion): AppErrorPayload => ({ name: 'MissingExpectedVersion', payload: missingExpectedVersion }),
+  BlobNotReferenced: (blobNotReferenced: AppError_BlobNotReferenced): AppErrorPayload => ({ name: 'BlobNotReferenced', payload: blobNotReferenced }),
+  InvalidQuota: (invalidQuota: AppError_InvalidQuota): AppErrorPayload => ({ name: 'InvalidQuota', payload: invalidQuota }),
+  InvalidTransition: (invalidTransition: AppError_InvalidTransition): AppErrorPayload => ({ name: 'InvalidTransition', payload: invalidTransition }),
+  NotPublished: (notPublished: AppError_NotPublished): AppErrorPayload => ({ name: 'NotPublished', payload: notPublished }),
+  ExpiryBeforePublish: (expiryBeforePublish: AppError_ExpiryBeforePublish): AppErrorPayload => ({ name: 'ExpiryBeforePublish', payload: expiryBeforePublish }),
+  Conflict: (conflict: AppError_Conflict): AppErrorPayload => ({ name: 'Conflict', payload: conflict }),
+  VersionConflict: (versionConflict: AppError_VersionConflict): AppErrorPayload => ({ name: 'VersionConflict', payload: versionConflict }),
+  OutOfRange: (outOfRange: AppError_OutOfRange): AppErrorPayload => ({ name: 'OutOfRange', payload: outOfRange }),
+  Quota: (quota: AppError_Quota): AppErrorPayload => ({ name: 'Quota', payload: quota }),
+  InvalidOp: (invalidOp: AppError_InvalidOp): AppErrorPayload => ({ name: 'InvalidOp', payload: invalidOp }),
+  BatchOutOfOrder: (batchOutOfOrder: AppError_BatchOutOfOrder): AppErrorPayload => ({ name: 'BatchOutOfOrder', payload: batchOutOfOrder }),
+  BatchExpired: (batchExpired: AppError_BatchExpired): AppErrorPayload => ({ name: 'BatchExpired', payload: batchExpired }),
+  Storage: (storage: AppError_Storage): AppErrorPayload => ({ name: 'Storage', payload: storage }),
+} as const;
+
+export interface AppError_BatchExpired {
+  client_id: string;
+  seq: number;
+}
+
+export interface AppError_BatchOutOfOrder {
+  client_id: string;
+  seq: number;
+  high_water: number;
+}
+
+export interface AppError_BlobNotReferenced {
+  hash: string;
+  document_id: string;
+}
+
+export interface AppError_Conflict {
+  kind: string;
+  id: string;
+}
+
+export interface AppError_Empty {
+  field: string;
+}
+
+export interface AppError_ExpiryBeforePublish {
+  publish_ms: number;
+  expires_ms: number;
+}
+
+export interface AppError_Forbidden {
+  required_role: typeof Role;
+}
+
+export interface AppError_InvalidMime {
+  mime: string;
+}
+
+export interface AppError_InvalidOp {
+  op_index: number;
+  reason: string;
+  doc_len: number;
+}
+
+export interface AppError_InvalidPattern {
+  pattern: string;
+}
+
+export interface AppError_InvalidQuota {
+  quota: string;
+}
+
+export interface AppError_InvalidTransition {
+  from: typeof DocStatus;
+  to: typeof DocStatus;
+}
+
+export interface AppError_LastOwner {
+  address: string;
+}
+
+export interface AppError_Leased {
+  lease_id: number;
+  holder: string;
+  span: LeaseRange | null;
+}
+
+export interface AppError_MissingExpectedVersion {
+  actual: number;
+}
+
+export interface AppError_NotCaller {
+  address: string;
+}
+
+export interface AppError_NotContextOwner {
+  owner: string;
+}
+
+export interface AppError_NotFound {
+  kind: string;
+  id: string;
+}
+
+export interface AppError_NotPublished {
+  document_id: string;
+}
+
+export interface AppError_OutOfRange {
+  index: number;
+  len: number;
+}
+
+export interface AppError_Quota {
+  quota: string;
+  limit: number;
+}
+
+export interface AppError_Storage {
+  message: string;
+}
+
+export interface AppError_VersionConflict {
+  expected: number;
+  actual: number;
+}
+
+export type ApplyModePayload =
+  | { name: 'Strict' }
+  | { name: 'Clamp' }
+
+export const ApplyMode = {
+  Strict: (): ApplyModePayload => ({ name: 'Strict' }),
+  Clamp: (): ApplyModePayload => ({ name: 'Clamp' }),
+} as const;
+
+export interface ApplyOptions {
+  batch_id: OpBatchId | null;
+  mode: typeof ApplyMode | null;
+  unit: typeof IndexUnit | null;
+}
+
+export interface AuditEntry {
+  seq: number;
+  method: string;
+  caller: string;
+  at_ms: number;
+  document_id: string | null;
+  version: number | null;
+  op_count: number;
+  detail: string | null;
+}
+
+export interface AuditFilter {
+  caller: string | null;
+  method: string | null;
+  document_id: string | null;
+  from_ms: number | null;
+  to_ms: number | null;
+}
+
+export interface AuditPage {
+  entries: AuditEntry[];
+  next_before_seq: number | null;
+}
+
+export interface AuthorStats {
+  author: string;
+  inserted: number;
+  deleted: number;
+  surviving: number;
+}
+
+export interface BlameRun {
+  text: string;
+  index: number;
+  len: number;
+  author: string;
+  created_ms: number;
+  version: number;
+}
+
+export interface BlobInfo {
+  hash: string;
+  size: number;
+  mime: string;
+  chunk_size: number;
+  chunk_count: number;
+  documents: string[];
+}
+
 export type DocOpPayload =
   | { name: 'Insert'; payload: DocOp_Insert }
   | { name: 'Delete'; payload: DocOp_Delete }
+  | { name: 'Replace'; payload: DocOp_Replace }
+  | { name: 'Move'; payload: DocOp_Move }
 
 export const DocOp = {
   Insert: (insert: DocOp_Insert): DocOpPayload => ({ name: 'Insert', payload: insert }),
   Delete: (delete_: DocOp_Delete): DocOpPayload => ({ name: 'Delete', payload: delete_ }),
+  Replace: (replace: DocOp_Replace): DocOpPayload => ({ name: 'Replace', payload: replace }),
+  Move: (move: DocOp_Move): DocOpPayload => ({ name: 'Move', payload: move }),
 } as const;
 
 export interface DocOp_Delete {
@@ -26,6 +256,31 @@ export interface DocOp_Insert {
   content: string;
 }
 
+export interface DocOp_Move {
+  from: number;
+  len: number;
+  to: number;
+}
+
+export interface DocOp_Replace {
+  index: number;
+  len: number;
+  content: string;
+}
+
+export type DocStatusPayload =
+  | { name: 'Draft' }
+  | { name: 'InReview' }
+  | { name: 'Published' }
+  | { name: 'Archived' }
+
+export const DocStatus = {
+  Draft: (): DocStatusPayload => ({ name: 'Draft' }),
+  InReview: (): DocStatusPayload => ({ name: 'InReview' }),
+  Published: (): DocStatusPayload => ({ name: 'Published' }),
+  Archived: (): DocStatusPayload => ({ name: 'Archived' }),
+} as const;
+
 export interface DocumentView {
   content: string;
   version: number;
@@ -33,19 +288,261 @@ export interface DocumentView {
   last_editor: string | null;
 }
 
+export interface Event_BlobReleased {
+  hash: string;
+  document_id: string;
+  remaining: number;
+}
+
+export interface Event_BlobStored {
+  hash: string;
+  size: number;
+  document_id: string;
+}
+
+export interface Event_DocumentCreated {
+  document_id: string;
+  content: string;
+  version: number;
+  stamp: HlcTimestamp;
+}
+
+export interface Event_DocumentPublished {
+  document_id: string;
+  version: number;
+  published_by: string;
+}
+
+export interface Event_DocumentReplaced {
+  document_id: string;
+  content: string;
+  version: number;
+  previous_version: number;
+  previous_hash: string;
+  editor: string;
+  stamp: HlcTimestamp;
+}
+
+export interface Event_DocumentUpdated {
+  document_id: string;
+  content: string;
+  version: number;
+  editor: string;
+  stamp: HlcTimestamp;
+}
+
+export interface Event_MediaRegistered {
+  asset_id: string;
+  uploader: string;
+}
+
+export interface Event_MediaUpdated {
+  asset_id: string;
+  alt: string;
+  caption: string;
+  version: number;
+  editor: string;
+}
+
+export interface Event_PublicationExpired {
+  document_id: string;
+  version: number;
+}
+
+export interface Event_StateMigrated {
+  from_schema: number;
+  to_schema: number;
+}
+
+export interface Event_StatusChanged {
+  document_id: string;
+  from: typeof DocStatus;
+  to: typeof DocStatus;
+  by: string;
+}
+
+export interface Event_UserMentioned {
+  recipient: string;
+  document_id: string;
+  mentioned_by: string;
+  notification_id: number;
+  version: number;
+}
+
+export interface Event_UserPing {
+  addr: string;
+  last_seen_ms: number;
+  stamp: HlcTimestamp;
+}
+
+export interface FindMatch {
+  index: number;
+  len: number;
+  start_anchor: string;
+  end_anchor: string;
+}
+
+export interface FindOptions {
+  regex: boolean | null;
+  case_insensitive: boolean | null;
+  whole_word: boolean | null;
+  unit: typeof IndexUnit | null;
+}
+
+export interface HlcTimestamp {
+  physical_ms: number;
+  logical: number;
+}
+
+export type IndexUnitPayload =
+  | { name: 'Scalar' }
+  | { name: 'Utf16' }
+  | { name: 'Grapheme' }
+
+export const IndexUnit = {
+  Scalar: (): IndexUnitPayload => ({ name: 'Scalar' }),
+  Utf16: (): IndexUnitPayload => ({ name: 'Utf16' }),
+  Grapheme: (): IndexUnitPayload => ({ name: 'Grapheme' }),
+} as const;
+
+export interface LeaseRange {
+  index: number;
+  len: number;
+}
+
+export interface LeaseView {
+  id: number;
+  holder: string;
+  range: LeaseRange | null;
+  expires_ms: number;
+}
+
+export interface MediaAsset {
+  asset_id: string;
+  url: string;
+  mime: string;
+  width: number | null;
+  height: number | null;
+  alt: string;
+  caption: string;
+  uploader: string;
+  checksum: string;
+  created_ms: number;
+  version: number;
+  last_editor: string;
+}
+
+export interface Member {
+  address: string;
+  role: typeof Role;
+}
+
+export interface NewMedia {
+  url: string;
+  mime: string;
+  width: number | null;
+  height: number | null;
+  alt: string | null;
+  caption: string | null;
+  checksum: string;
+}
+
+export interface Notification {
+  id: number;
+  recipient: string;
+  document_id: string;
+  mentioned_by: string;
+  version: number;
+  excerpt: string;
+  created_ms: number;
+  read: boolean;
+}
+
+export interface OpBatchId {
+  client_id: string;
+  seq: number;
+}
+
 export interface PresenceEntry {
   address: string;
   last_seen_ms: number;
   payload: string;
 }
 
+export interface PublishedView {
+  document_id: string;
+  content: string;
+  version: number;
+  published_ms: number;
+  published_by: string;
+}
+
+export interface Quotas {
+  max_doc_len: number;
+  max_ops_per_call: number;
+  max_insert_len: number;
+  max_documents: number;
+  max_presence_entries: number;
+  max_payload_bytes: number;
+  max_blob_bytes: number;
+  max_notifications: number;
+  max_batch_clients: number;
+  max_audit_entries: number;
+}
+
+export type RolePayload =
+  | { name: 'Viewer' }
+  | { name: 'Editor' }
+  | { name: 'Reviewer' }
+  | { name: 'Owner' }
+
+export const Role = {
+  Viewer: (): RolePayload => ({ name: 'Viewer' }),
+  Editor: (): RolePayload => ({ name: 'Editor' }),
+  Reviewer: (): RolePayload => ({ name: 'Reviewer' }),
+  Owner: (): RolePayload => ({ name: 'Owner' }),
+} as const;
+
+export interface SearchHit {
+  document_id: string;
+  snippet: string;
+  snippet_start: number;
+  matches: SearchMatch[];
+}
+
+export interface SearchMatch {
+  index: number;
+  len: number;
+}
+
+export interface TemplateVar {
+  name: string;
+  value: string;
+}
+
+export interface TemplateView {
+  template_id: string;
+  name: string;
+  variables: string[];
+}
+
 
 
 
 export type AbiEvent =
-  | { name: "DocumentCreated" }
-  | { name: "DocumentUpdated" }
-  | { name: "UserPing" }
+  | { name: "DocumentCreated"; payload: Event_DocumentCreated }
+  | { name: "DocumentReplaced"; payload: Event_DocumentReplaced }
+  | { name: "DocumentUpdated"; payload: Event_DocumentUpdated }
+  | { name: "UserPing"; payload: Event_UserPing }
+  | { name: "StatusChanged"; payload: Event_StatusChanged }
+  | { name: "DocumentPublished"; payload: Event_DocumentPublished }
+  | { name: "PublicationExpired"; payload: Event_PublicationExpired }
+  | { name: "MediaRegistered"; payload: Event_MediaRegistered }
+  | { name: "MediaUpdated"; payload: Event_MediaUpdated }
+  | { name: "BlobStored"; payload: Event_BlobStored }
+  | { name: "BlobReleased"; payload: Event_BlobReleased }
+  | { name: "UserMentioned"; payload: Event_UserMentioned }
+  | { name: "StateMigrated"; payload: Event_StateMigrated }
 ;
 
 
@@ -169,7 +666,7 @@ export class AbiClient {
   /**
    * create_document
    */
-  public async createDocument(params: { content: string }): Promise<number> {
+  public async createDocument(params: { content: string; expected_version: number | null; overwrite: boolean | null; document_id: string | null }): Promise<number> {
     const response = await this.app.execute(this.context, 'create_document', params);
     if (response.success) {
       return response.result as number;
@@ -178,10 +675,178 @@ export class AbiClient {
     }
   }
 
+  /**
+   * instantiate_template
+   */
+  public async instantiateTemplate(params: { template_id: string; vars: TemplateVar[]; document_id: string | null }): Promise<string> {
+    const response = await this.app.execute(this.context, 'instantiate_template', params);
+    if (response.success) {
+      return response.result as string;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * duplicate_document
+   */
+  public async duplicateDocument(params: { document_id: string; new_document_id: string | null }): Promise<string> {
+    const response = await this.app.execute(this.context, 'duplicate_document', params);
+    if (response.success) {
+      return response.result as string;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * mark_template
+   */
+  public async markTemplate(params: { name: string | null; document_id: string | null }): Promise<void> {
+    const response = await this.app.execute(this.context, 'mark_template', params);
+    if (response.success) {
+      return response.result as void;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * list_templates
+   */
+  public async listTemplates(): Promise<TemplateView[]> {
+    const response = await this.app.execute(this.context, 'list_templates', {});
+    if (response.success) {
+      return response.result as TemplateView[];
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * register_media
+   */
+  public async registerMedia(params: { media: NewMedia }): Promise<MediaAsset> {
+    const response = await this.app.execute(this.context, 'register_media', params);
+    if (response.success) {
+      return response.result as MediaAsset;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * update_media
+   */
+  public async updateMedia(params: { asset_id: string; alt: string | null; caption: string | null; expected_version: number | null }): Promise<number> {
+    const response = await this.app.execute(this.context, 'update_media', params);
+    if (response.success) {
+      return response.result as number;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * get_media
+   */
+  public async getMedia(params: { asset_id: string }): Promise<MediaAsset> {
+    const response = await this.app.execute(this.context, 'get_media', params);
+    if (response.success) {
+      return response.result as MediaAsset;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * list_media
+   */
+  public async listMedia(params: { document_id: string | null }): Promise<MediaAsset[]> {
+    const response = await this.app.execute(this.context, 'list_media', params);
+    if (response.success) {
+      return response.result as MediaAsset[];
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * find_unreferenced_media
+   */
+  public async findUnreferencedMedia(): Promise<MediaAsset[]> {
+    const response = await this.app.execute(this.context, 'find_unreferenced_media', {});
+    if (response.success) {
+      return response.result as MediaAsset[];
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * put_blob
+   */
+  public async putBlob(params: { data: CalimeroBytes; mime: string; document_id: string | null }): Promise<BlobInfo> {
+    const response = await this.app.execute(this.context, 'put_blob', convertCalimeroBytesForWasm(params));
+    if (response.success) {
+      return response.result as BlobInfo;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * get_blob
+   */
+  public async getBlob(params: { hash: string }): Promise<BlobInfo> {
+    const response = await this.app.execute(this.context, 'get_blob', params);
+    if (response.success) {
+      return response.result as BlobInfo;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * get_blob_chunk
+   */
+  public async getBlobChunk(params: { hash: string; index: number }): Promise<CalimeroBytes> {
+    const response = await this.app.execute(this.context, 'get_blob_chunk', params);
+    if (response.success) {
+      return convertWasmResultToCalimeroBytes(response.result) as CalimeroBytes;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * list_blobs
+   */
+  public async listBlobs(params: { document_id: string | null }): Promise<BlobInfo[]> {
+    const response = await this.app.execute(this.context, 'list_blobs', params);
+    if (response.success) {
+      return response.result as BlobInfo[];
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * release_blob
+   */
+  public async releaseBlob(params: { hash: string; document_id: string | null }): Promise<number> {
+    const response = await this.app.execute(this.context, 'release_blob', params);
+    if (response.success) {
+      return response.result as number;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
   /**
    * apply_ops
    */
-  public async applyOps(params: { ops: typeof DocOp[]; expected_version: number | null }): Promise<number> {
+  public async applyOps(params: { ops: typeof DocOp[]; expected_version: number | null; options: ApplyOptions | null; document_id: string | null }): Promise<number> {
     const response = await this.app.execute(this.context, 'apply_ops', params);
     if (response.success) {
       return response.result as number;
@@ -190,11 +855,311 @@ export class AbiClient {
     }
   }
 
+  /**
+   * find_in_document
+   */
+  public async findInDocument(params: { pattern: string; options: FindOptions | null; document_id: string | null }): Promise<FindMatch[]> {
+    const response = await this.app.execute(this.context, 'find_in_document', params);
+    if (response.success) {
+      return response.result as FindMatch[];
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * replace_all
+   */
+  public async replaceAll(params: { pattern: string; replacement: string; expected_version: number | null; options: FindOptions | null; document_id: string | null }): Promise<number> {
+    const response = await this.app.execute(this.context, 'replace_all', params);
+    if (response.success) {
+      return response.result as number;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * undo
+   */
+  public async undo(params: { document_id: string | null }): Promise<number> {
+    const response = await this.app.execute(this.context, 'undo', params);
+    if (response.success) {
+      return response.result as number;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * redo
+   */
+  public async redo(params: { document_id: string | null }): Promise<number> {
+    const response = await this.app.execute(this.context, 'redo', params);
+    if (response.success) {
+      return response.result as number;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * acquire_lease
+   */
+  public async acquireLease(params: { range: LeaseRange | null; ttl_ms: number; document_id: string | null }): Promise<LeaseView> {
+    const response = await this.app.execute(this.context, 'acquire_lease', params);
+    if (response.success) {
+      return response.result as LeaseView;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * release_lease
+   */
+  public async releaseLease(params: { lease_id: number; document_id: string | null }): Promise<void> {
+    const response = await this.app.execute(this.context, 'release_lease', params);
+    if (response.success) {
+      return response.result as void;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * get_leases
+   */
+  public async getLeases(params: { document_id: string | null }): Promise<LeaseView[]> {
+    const response = await this.app.execute(this.context, 'get_leases', params);
+    if (response.success) {
+      return response.result as LeaseView[];
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * search
+   */
+  public async search(params: { query: string; limit: number }): Promise<SearchHit[]> {
+    const response = await this.app.execute(this.context, 'search', params);
+    if (response.success) {
+      return response.result as SearchHit[];
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * set_role
+   */
+  public async setRole(params: { address: string; role: typeof Role | null; document_id: string | null }): Promise<void> {
+    const response = await this.app.execute(this.context, 'set_role', params);
+    if (response.success) {
+      return response.result as void;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * get_roles
+   */
+  public async getRoles(params: { document_id: string | null }): Promise<Member[]> {
+    const response = await this.app.execute(this.context, 'get_roles', params);
+    if (response.success) {
+      return response.result as Member[];
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * get_status
+   */
+  public async getStatus(params: { document_id: string | null }): Promise<typeof DocStatus> {
+    const response = await this.app.execute(this.context, 'get_status', params);
+    if (response.success) {
+      return response.result as typeof DocStatus;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * set_status
+   */
+  public async setStatus(params: { status: typeof DocStatus; document_id: string | null }): Promise<void> {
+    const response = await this.app.execute(this.context, 'set_status', params);
+    if (response.success) {
+      return response.result as void;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * publish
+   */
+  public async publish(params: { document_id: string | null }): Promise<PublishedView> {
+    const response = await this.app.execute(this.context, 'publish', params);
+    if (response.success) {
+      return response.result as PublishedView;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * schedule_publish
+   */
+  public async schedulePublish(params: { publish_at_ms: number; expires_at_ms: number | null; document_id: string | null }): Promise<void> {
+    const response = await this.app.execute(this.context, 'schedule_publish', params);
+    if (response.success) {
+      return response.result as void;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * set_expiry
+   */
+  public async setExpiry(params: { expires_at_ms: number | null; document_id: string | null }): Promise<void> {
+    const response = await this.app.execute(this.context, 'set_expiry', params);
+    if (response.success) {
+      return response.result as void;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * run_schedules
+   */
+  public async runSchedules(): Promise<number> {
+    const response = await this.app.execute(this.context, 'run_schedules', {});
+    if (response.success) {
+      return response.result as number;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * get_published
+   */
+  public async getPublished(params: { document_id: string | null }): Promise<PublishedView> {
+    const response = await this.app.execute(this.context, 'get_published', params);
+    if (response.success) {
+      return response.result as PublishedView;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * get_blame
+   */
+  public async getBlame(params: { document_id: string | null }): Promise<BlameRun[]> {
+    const response = await this.app.execute(this.context, 'get_blame', params);
+    if (response.success) {
+      return response.result as BlameRun[];
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * get_contributions
+   */
+  public async getContributions(params: { document_id: string | null }): Promise<AuthorStats[]> {
+    const response = await this.app.execute(this.context, 'get_contributions', params);
+    if (response.success) {
+      return response.result as AuthorStats[];
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * list_notifications
+   */
+  public async listNotifications(params: { unread_only: boolean }): Promise<Notification[]> {
+    const response = await this.app.execute(this.context, 'list_notifications', params);
+    if (response.success) {
+      return response.result as Notification[];
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * mark_read
+   */
+  public async markRead(params: { notification_ids: number[] | null }): Promise<number> {
+    const response = await this.app.execute(this.context, 'mark_read', params);
+    if (response.success) {
+      return response.result as number;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * get_audit_log
+   */
+  public async getAuditLog(params: { filter: AuditFilter | null; before_seq: number | null; limit: number }): Promise<AuditPage> {
+    const response = await this.app.execute(this.context, 'get_audit_log', params);
+    if (response.success) {
+      return response.result as AuditPage;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * get_quotas
+   */
+  public async getQuotas(): Promise<Quotas> {
+    const response = await this.app.execute(this.context, 'get_quotas', {});
+    if (response.success) {
+      return response.result as Quotas;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * set_quotas
+   */
+  public async setQuotas(params: { quotas: Quotas }): Promise<void> {
+    const response = await this.app.execute(this.context, 'set_quotas', params);
+    if (response.success) {
+      return response.result as void;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
+  /**
+   * migrate
+   */
+  public async migrate(): Promise<number> {
+    const response = await this.app.execute(this.context, 'migrate', {});
+    if (response.success) {
+      return response.result as number;
+    } else {
+      throw new Error(response.error || 'Execution failed');
+    }
+  }
+
   /**
    * get_document
    */
-  public async getDocument(): Promise<DocumentView> {
-    const response = await this.app.execute(this.context, 'get_document', {});
+  public async getDocument(params: { document_id: string | null }): Promise<DocumentView> {
+    const response = await this.app.execute(this.context, 'get_document', params);
     if (response.success) {
       return response.result as DocumentView;
     } else {
@@ -1,6 +1,9 @@
 import React, { ChangeEvent, Fragment, useCallback, useRef } from "react";
 
+import { useCalimero } from "@calimero-network/calimero-client";
+
 import MediaLibrary from "@/components/media-library";
+import mediaService from "@/services/media";
 
 import { useImage } from "../../hooks/use-image";
 import useModal from "../../hooks/use-modal";
@@ -10,6 +13,7 @@ import Dialog from "../ui/dialog";
 const ImageButton = () => {
   const { canInsert, insert } = useImage();
   const { open, handleOpen, handleClose } = useModal();
+  const { app } = useCalimero();
 
   return (
     <>
@@ -22,11 +26,19 @@ const ImageButton = () => {
       <Dialog open={open} onOpenChange={handleClose}>
         <MediaLibrary
           onClose={handleClose}
-          onInsert={(image) => {
+          onInsert={async (image) => {
+            // Without a registered asset the image still goes in, just untracked by the media registry
+            const asset = app
+              ? await mediaService.registerImage(app, image).catch((err) => {
+                  console.warn("[ImageButton] register_media failed", err);
+                  return null;
+                })
+              : null;
             insert({
               src: image.url,
               width: image.width,
               height: image.height,
+              mediaId: asset?.asset_id,
             });
             handleClose();
           }}
@@ -19,6 +19,7 @@ declare module "@tiptap/core" {
         width?: number;
         height?: number;
         caption?: string;
+        mediaId?: string;
       }) => ReturnType;
       imageToFigure: () => ReturnType;
       figureToImage: () => ReturnType;
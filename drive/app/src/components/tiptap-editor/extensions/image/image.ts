@@ -15,6 +15,13 @@ export const Image = TiptapImage.extend({
         parseHTML: (element: HTMLElement) => element.getAttribute("alt"),
         renderHTML: (attrs) => (attrs.alt ? { alt: attrs.alt } : {}),
       },
+      // id of the asset in the context's media registry, which finds references through this attribute
+      mediaId: {
+        default: null,
+        parseHTML: (element: HTMLElement) => element.getAttribute("data-media-id"),
+        renderHTML: (attrs) =>
+          attrs.mediaId ? { "data-media-id": attrs.mediaId } : {},
+      },
       caption: {
         default: null,
         parseHTML: (element: HTMLElement) => {
@@ -12,6 +12,7 @@ export type ImageAttributes = {
   width?: number;
   height?: number;
   caption?: string;
+  mediaId?: string;
 };
 
 export type ImageData = ImageAttributes & {
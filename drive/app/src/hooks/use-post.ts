@@ -45,7 +45,7 @@ export function usePost() {
         }
         // fetch active match id if any
         try {
-          const documentData = await client.getDocument();
+          const documentData = await client.getDocument({ document_id: null });
           if (documentData) {
             console.log(
               "[usePost] fetched document data during init",
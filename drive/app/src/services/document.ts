@@ -48,9 +48,9 @@ export async function createDocument(app: CalimeroApp, content: string) {
   return await tryCall(api, ['create_document', 'createDocument'], { content });
 }
 
-export async function applyOps(app: CalimeroApp, ops: any[], expected_version?: number | null) {
+export async function applyOps(app: CalimeroApp, ops: any[], expected_version?: number | null, options?: any) {
   const api = await getApi(app);
-  return await tryCall(api, ['apply_ops', 'applyOps', 'apply_operations'], { ops, expected_version });
+  return await tryCall(api, ['apply_ops', 'applyOps', 'apply_operations'], { ops, expected_version, options: options ?? null });
 }
 
 export default {
@@ -0,0 +1,41 @@
+import { CalimeroApp } from '@calimero-network/calimero-client';
+
+import type { MediaAsset } from '@/api/AbiClient';
+
+import { createKvClient } from '../features/kv/api';
+
+export interface UploadedImage {
+  url: string;
+  format: string;
+  width: number;
+  height: number;
+}
+
+// Digest of the uploaded bytes, in the `sha256:<hex>` form the backend uses for hashes
+async function checksumOf(url: string): Promise<string> {
+  const response = await fetch(url);
+  if (!response.ok) throw new Error(`Failed to fetch ${url}`);
+  const digest = await crypto.subtle.digest('SHA-256', await response.arrayBuffer());
+  return 'sha256:' + Array.from(new Uint8Array(digest), (b) => b.toString(16).padStart(2, '0')).join('');
+}
+
+// Record an uploaded image in the context's media registry; registering the same upload again returns the same asset
+export async function registerImage(app: CalimeroApp, image: UploadedImage): Promise<MediaAsset> {
+  const api = await createKvClient(app);
+  const checksum = await checksumOf(image.url);
+  return await api.registerMedia({
+    media: {
+      url: image.url,
+      mime: `image/${image.format}`,
+      width: image.width ?? null,
+      height: image.height ?? null,
+      alt: null,
+      caption: null,
+      checksum,
+    },
+  });
+}
+
+export default {
+  registerImage,
+};
@@ -4,6 +4,7 @@ import { mockData } from "@/mock";
 
 import documentService from './document';
 
+import { IndexUnit } from '@/api/AbiClient';
 import type { DocOpPayload, DocOp_Insert, DocOp_Delete } from '@/api/AbiClient';
 
 const mock = mockData.html;
@@ -95,10 +96,12 @@ const savePost = async (data: Partial<Post>, app?: CalimeroApp): Promise<void> =
         ops.push({ name: 'Insert', payload: ins });
 
         const expectVersion = typeof current.version === 'number' ? current.version : undefined;
+        // `String.length` counts UTF-16 code units, so have the backend count offsets the same way
+        const options = { batch_id: null, mode: null, unit: IndexUnit.Utf16() };
 
         try {
           console.log(`[post.save] attempt ${attempt}: applying ops`, ops, 'expectVersion=', expectVersion);
-          await documentService.applyOps(app, ops as any, expectVersion ?? null);
+          await documentService.applyOps(app, ops as any, expectVersion ?? null, options);
           return;
         } catch (err) {
           console.warn(`[post.save] applyOps failed (attempt ${attempt}):`, err);
@@ -1,29 +1,151 @@
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use calimero_wasm_abi::emitter::emit_manifest;
 
-fn main() {
-    println!("cargo:rerun-if-changed=src/lib.rs");
+#[path = "build/abi_compat.rs"]
+mod abi_compat;
 
-    // Parse the source code
+fn main() {
+    // Parse the source code, inlining every `mod foo;` so the emitter sees
+    // types and events declared in module files as well
     let src_path = Path::new("src/lib.rs");
-    let src_content = fs::read_to_string(src_path).expect("Failed to read src/lib.rs");
+    let src_content = expand_modules(src_path);
 
     // Generate ABI manifest using the emitter
     let manifest = emit_manifest(&src_content).expect("Failed to emit ABI manifest");
 
     // Serialize the manifest to JSON
     let json = serde_json::to_string_pretty(&manifest).expect("Failed to serialize manifest");
+    let emitted: serde_json::Value = serde_json::from_str(&json).expect("Failed to parse emitted ABI JSON");
 
-    // Write the ABI JSON to the res directory
+    // The committed manifest is what clients are generated from, so a build
+    // only checks it; rewriting it takes an explicit `UPDATE_ABI=1`
     let res_dir = Path::new("res");
+    let abi_path = res_dir.join("abi.json");
+    let version_path = res_dir.join("abi.version");
+    let version = format!("{}\n", env!("CARGO_PKG_VERSION"));
+    println!("cargo:rerun-if-changed={}", abi_path.display());
+    println!("cargo:rerun-if-changed={}", version_path.display());
+    println!("cargo:rerun-if-env-changed=UPDATE_ABI");
+
+    let committed = fs::read_to_string(&abi_path).ok().and_then(|old| serde_json::from_str::<serde_json::Value>(&old).ok());
+    let recorded = fs::read_to_string(&version_path).ok();
+    if committed.as_ref() == Some(&emitted) && recorded.as_deref() == Some(version.as_str()) {
+        return;
+    }
+    if std::env::var_os("UPDATE_ABI").is_none() {
+        panic!(
+            "res/abi.json or res/abi.version is out of date with the source.\n\
+             Rebuild with UPDATE_ABI=1 to regenerate them, then commit both files and regenerate the client."
+        );
+    }
+
+    check_compatibility(&abi_path, &version_path, &json);
     if !res_dir.exists() {
         fs::create_dir_all(res_dir).expect("Failed to create res directory");
     }
-
-    let abi_path = res_dir.join("abi.json");
     fs::write(&abi_path, json).expect("Failed to write ABI JSON");
+    fs::write(&version_path, version).expect("Failed to write ABI version");
+}
 
-    println!("cargo:rerun-if-changed={}", abi_path.display());
+/// Compare the freshly emitted manifest with the committed one and fail the
+/// build on breaking changes unless the crate version was bumped in a
+/// semver-incompatible way since the committed manifest was generated.
+fn check_compatibility(abi_path: &Path, version_path: &Path, new_json: &str) {
+    let Ok(old_json) = fs::read_to_string(abi_path) else {
+        return;
+    };
+    let old: serde_json::Value = serde_json::from_str(&old_json).expect("Failed to parse committed ABI JSON");
+    let new: serde_json::Value = serde_json::from_str(new_json).expect("Failed to parse emitted ABI JSON");
+
+    let report = abi_compat::compare(&old, &new);
+    for change in &report.additive {
+        println!("cargo:warning=ABI additive change: {change}");
+    }
+    if report.breaking.is_empty() {
+        return;
+    }
+
+    let current = env!("CARGO_PKG_VERSION");
+    // Manifests committed before the version file existed belong to the current version
+    let recorded = fs::read_to_string(version_path).unwrap_or_else(|_| current.to_string());
+    if abi_compat::is_breaking_bump(&recorded, current) {
+        for change in &report.breaking {
+            println!("cargo:warning=ABI breaking change accepted by bump {} -> {current}: {change}", recorded.trim());
+        }
+        return;
+    }
+
+    panic!(
+        "Breaking ABI changes against res/abi.json (generated by version {}):\n  - {}\n\
+         Bump the crate version in a semver-incompatible way to accept them.",
+        recorded.trim(),
+        report.breaking.join("\n  - ")
+    );
+}
+
+/// Parse `path` and inline each out-of-line `mod name;` declaration with
+/// the (recursively expanded) module file, following rustc's lookup rules
+/// for `name.rs` and `name/mod.rs`. Modules only compiled for tests are
+/// dropped, since nothing in them is part of the ABI.
+fn expand_modules(path: &Path) -> String {
+    prettyplease::unparse(&parse_module(path))
+}
+
+fn parse_module(path: &Path) -> syn::File {
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let content = fs::read_to_string(path).unwrap_or_else(|e| panic!("Failed to read {}: {e}", path.display()));
+    let mut file = syn::parse_file(&content).unwrap_or_else(|e| panic!("Failed to parse {}: {e}", path.display()));
+    expand_items(&mut file.items, &module_dir(path));
+    file
+}
+
+fn expand_items(items: &mut Vec<syn::Item>, dir: &Path) {
+    items.retain(|item| !matches!(item, syn::Item::Mod(module) if is_test_only(&module.attrs)));
+    for item in items.iter_mut() {
+        let syn::Item::Mod(module) = item else {
+            continue;
+        };
+        let name = module.ident.to_string();
+        match &mut module.content {
+            Some((_, inner)) => expand_items(inner, &dir.join(&name)),
+            None => {
+                let file = parse_module(&module_file(dir, &name));
+                // The file's inner attributes, such as its `//!` docs, move into the block
+                module.attrs.extend(file.attrs);
+                module.content = Some((Default::default(), file.items));
+                module.semi = None;
+            }
+        }
+    }
+}
+
+fn is_test_only(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident("cfg") && attr.parse_args::<syn::Ident>().is_ok_and(|arg| arg == "test"))
+}
+
+/// Directory holding the children of the module defined by `path`: the
+/// file's own directory for `lib.rs`/`mod.rs`, otherwise a sibling directory
+/// named after the file.
+fn module_dir(path: &Path) -> PathBuf {
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    match path.file_stem().and_then(|s| s.to_str()) {
+        Some("lib") | Some("main") | Some("mod") => parent.to_path_buf(),
+        Some(stem) => parent.join(stem),
+        None => parent.to_path_buf(),
+    }
+}
+
+fn module_file(dir: &Path, name: &str) -> PathBuf {
+    let flat = dir.join(format!("{name}.rs"));
+    if flat.exists() {
+        return flat;
+    }
+    let nested = dir.join(name).join("mod.rs");
+    if nested.exists() {
+        return nested;
+    }
+    panic!("Failed to locate module `{name}` in {}", dir.display());
 }
@@ -0,0 +1,195 @@
+//! Compatibility check between a freshly emitted ABI manifest and the one
+//! committed in `res/abi.json`.
+//!
+//! Changes are classified as additive (new methods, types, events, variants,
+//! or new nullable params/fields) or breaking (anything removed, renamed or
+//! retyped, and new required params/fields). Breaking changes are only
+//! accepted together with a semver-incompatible bump of the crate version
+//! recorded next to the manifest in `res/abi.version`.
+
+use serde_json::{Map, Value};
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub additive: Vec<String>,
+    pub breaking: Vec<String>,
+}
+
+/// Compare two manifests and collect every difference.
+pub fn compare(old: &Value, new: &Value) -> Report {
+    let mut report = Report::default();
+    compare_methods(old, new, &mut report);
+    compare_types(old, new, &mut report);
+    compare_events(old, new, &mut report);
+    report
+}
+
+/// Whether going from `old` to `new` is a semver-incompatible version bump:
+/// a major bump, or a minor bump while still on `0.x`.
+pub fn is_breaking_bump(old: &str, new: &str) -> bool {
+    let (Some(old), Some(new)) = (parse_version(old), parse_version(new)) else {
+        return false;
+    };
+    if old.0 == 0 && new.0 == 0 {
+        new.1 > old.1
+    } else {
+        new.0 > old.0
+    }
+}
+
+fn parse_version(v: &str) -> Option<(u64, u64)> {
+    let mut parts = v.trim().split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+fn named<'a>(manifest: &'a Value, key: &str) -> Vec<(&'a str, &'a Value)> {
+    manifest
+        .get(key)
+        .and_then(Value::as_array)
+        .map(|items| items.iter().filter_map(|item| Some((item.get("name")?.as_str()?, item))).collect())
+        .unwrap_or_default()
+}
+
+fn lookup<'a>(items: &[(&str, &'a Value)], name: &str) -> Option<&'a Value> {
+    items.iter().find(|(n, _)| *n == name).map(|(_, v)| *v)
+}
+
+fn is_nullable(item: &Value) -> bool {
+    item.get("nullable").and_then(Value::as_bool).unwrap_or(false)
+}
+
+fn compare_methods(old: &Value, new: &Value, report: &mut Report) {
+    let old_methods = named(old, "methods");
+    let new_methods = named(new, "methods");
+
+    for (name, old_method) in &old_methods {
+        let Some(new_method) = lookup(&new_methods, name) else {
+            report.breaking.push(format!("method `{name}` removed"));
+            continue;
+        };
+        let context = format!("method `{name}`");
+        compare_members(&context, "param", old_method.get("params"), new_method.get("params"), report);
+        if old_method.get("returns") != new_method.get("returns") || is_nullable_return(old_method) != is_nullable_return(new_method) {
+            report.breaking.push(format!("{context} return type changed"));
+        }
+    }
+    for (name, _) in &new_methods {
+        if lookup(&old_methods, name).is_none() {
+            report.additive.push(format!("method `{name}` added"));
+        }
+    }
+}
+
+fn is_nullable_return(method: &Value) -> bool {
+    method.get("returns_nullable").and_then(Value::as_bool).unwrap_or(false)
+}
+
+/// Compare positional members (method params or record fields). Members are
+/// matched by position so renames show up as breaking.
+fn compare_members(context: &str, what: &str, old: Option<&Value>, new: Option<&Value>, report: &mut Report) {
+    let empty = Vec::new();
+    let old = old.and_then(Value::as_array).unwrap_or(&empty);
+    let new = new.and_then(Value::as_array).unwrap_or(&empty);
+
+    for (i, old_member) in old.iter().enumerate() {
+        let old_name = old_member.get("name").and_then(Value::as_str).unwrap_or("?");
+        let Some(new_member) = new.get(i) else {
+            report.breaking.push(format!("{context}: {what} `{old_name}` removed"));
+            continue;
+        };
+        let new_name = new_member.get("name").and_then(Value::as_str).unwrap_or("?");
+        if old_name != new_name {
+            report.breaking.push(format!("{context}: {what} `{old_name}` renamed to `{new_name}`"));
+        }
+        if old_member.get("type") != new_member.get("type") {
+            report.breaking.push(format!("{context}: {what} `{old_name}` changed type"));
+        }
+        match (is_nullable(old_member), is_nullable(new_member)) {
+            (true, false) => report.breaking.push(format!("{context}: {what} `{old_name}` is no longer nullable")),
+            (false, true) => report.additive.push(format!("{context}: {what} `{old_name}` became nullable")),
+            _ => {}
+        }
+    }
+    for new_member in new.iter().skip(old.len()) {
+        let name = new_member.get("name").and_then(Value::as_str).unwrap_or("?");
+        if is_nullable(new_member) {
+            report.additive.push(format!("{context}: nullable {what} `{name}` added"));
+        } else {
+            report.breaking.push(format!("{context}: required {what} `{name}` added"));
+        }
+    }
+}
+
+fn compare_types(old: &Value, new: &Value, report: &mut Report) {
+    let empty = Map::new();
+    let old_types = old.get("types").and_then(Value::as_object).unwrap_or(&empty);
+    let new_types = new.get("types").and_then(Value::as_object).unwrap_or(&empty);
+
+    for (name, old_ty) in old_types {
+        let Some(new_ty) = new_types.get(name) else {
+            report.breaking.push(format!("type `{name}` removed"));
+            continue;
+        };
+        let context = format!("type `{name}`");
+        if old_ty.get("kind") != new_ty.get("kind") {
+            report.breaking.push(format!("{context} changed kind"));
+            continue;
+        }
+        match old_ty.get("kind").and_then(Value::as_str) {
+            Some("record") => compare_members(&context, "field", old_ty.get("fields"), new_ty.get("fields"), report),
+            Some("variant") => compare_variants(&context, old_ty, new_ty, report),
+            _ => {
+                if old_ty != new_ty {
+                    report.breaking.push(format!("{context} changed"));
+                }
+            }
+        }
+    }
+    for name in new_types.keys() {
+        if !old_types.contains_key(name) {
+            report.additive.push(format!("type `{name}` added"));
+        }
+    }
+}
+
+fn compare_variants(context: &str, old: &Value, new: &Value, report: &mut Report) {
+    let old_variants = named(old, "variants");
+    let new_variants = named(new, "variants");
+
+    for (name, old_variant) in &old_variants {
+        match lookup(&new_variants, name) {
+            None => report.breaking.push(format!("{context}: variant `{name}` removed or renamed")),
+            Some(new_variant) if old_variant.get("payload") != new_variant.get("payload") => {
+                report.breaking.push(format!("{context}: variant `{name}` changed payload"));
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, _) in &new_variants {
+        if lookup(&old_variants, name).is_none() {
+            report.additive.push(format!("{context}: variant `{name}` added"));
+        }
+    }
+}
+
+fn compare_events(old: &Value, new: &Value, report: &mut Report) {
+    let old_events = named(old, "events");
+    let new_events = named(new, "events");
+
+    for (name, old_event) in &old_events {
+        match lookup(&new_events, name) {
+            None => report.breaking.push(format!("event `{name}` removed or renamed")),
+            Some(new_event) if old_event.get("payload") != new_event.get("payload") => {
+                report.breaking.push(format!("event `{name}` changed payload"));
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, _) in &new_events {
+        if lookup(&old_events, name).is_none() {
+            report.additive.push(format!("event `{name}` added"));
+        }
+    }
+}
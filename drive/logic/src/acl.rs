@@ -0,0 +1,68 @@
+//! Per-document roles.
+//!
+//! Roles are ranked, and each one can do everything the ones below it can:
+//! a reviewer can also edit and an owner can also review. Whoever first
+//! writes a document's content becomes its owner; owners grant and revoke
+//! roles for everyone else.
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+
+use crate::document::Document;
+use crate::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub enum Role {
+    Viewer,
+    /// Writes drafts and submits them for review
+    Editor,
+    /// Publishes reviewed drafts or sends them back
+    Reviewer,
+    /// Manages roles and archives documents
+    Owner,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct Member {
+    pub address: String,
+    pub role: Role,
+}
+
+impl Document {
+    pub(crate) fn role_of(&self, address: &str) -> Option<Role> {
+        self.members.iter().find(|m| m.address == address).map(|m| m.role)
+    }
+
+    pub(crate) fn require_role(&self, address: &str, role: Role) -> Result<(), AppError> {
+        match self.role_of(address) {
+            Some(held) if held >= role => Ok(()),
+            _ => Err(AppError::Forbidden { required_role: role }),
+        }
+    }
+
+    /// Make `address` the owner of a document nobody owns yet.
+    pub(crate) fn claim(&mut self, address: &str) {
+        if !self.members.iter().any(|m| m.role == Role::Owner) {
+            self.members.retain(|m| m.address != address);
+            self.members.push(Member { address: address.to_string(), role: Role::Owner });
+        }
+    }
+
+    /// Give `address` `role`, or take its role away when `None`. A document
+    /// always keeps at least one owner.
+    pub(crate) fn assign_role(&mut self, address: &str, role: Option<Role>) -> Result<(), AppError> {
+        let owners = self.members.iter().filter(|m| m.role == Role::Owner && m.address != address).count();
+        if owners == 0 && role != Some(Role::Owner) {
+            return Err(AppError::LastOwner { address: address.to_string() });
+        }
+        self.members.retain(|m| m.address != address);
+        if let Some(role) = role {
+            self.members.push(Member { address: address.to_string(), role });
+        }
+        Ok(())
+    }
+}
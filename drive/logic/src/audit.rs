@@ -0,0 +1,108 @@
+//! Append-only log of the calls that changed the state.
+//!
+//! Every successful call that changed something appends one entry naming
+//! the method, the caller, the document it acted on and the version it
+//! left, with the number of edit ops where that applies. Calls that turn
+//! out to be no-ops are not logged. Entries are never rewritten; once the
+//! log holds `Quotas::max_audit_entries` the oldest are dropped, and `seq`
+//! keeps counting. Presence pings are not logged; they change nothing but the
+//! caller's own presence entry and would drown out everything else.
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+
+use crate::{encode_executor_address, env, SharedDocument};
+
+/// Most entries returned by one `get_audit_log` page.
+pub(crate) const MAX_AUDIT_PAGE: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct AuditEntry {
+    /// Position in the log, from 1
+    pub seq: u64,
+    pub method: String,
+    pub caller: String,
+    pub at_ms: u64,
+    /// Document the call acted on, if it acted on one
+    pub document_id: Option<String>,
+    /// Version of that document after the call
+    pub version: Option<u64>,
+    /// Edit ops applied by the call
+    pub op_count: u32,
+    /// What else the call changed, e.g. the role granted by `set_role`
+    pub detail: Option<String>,
+}
+
+/// Which entries `get_audit_log` returns; every field narrows the result.
+#[derive(Debug, Clone, Default, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct AuditFilter {
+    pub caller: Option<String>,
+    pub method: Option<String>,
+    pub document_id: Option<String>,
+    /// Inclusive lower bound on `at_ms`
+    pub from_ms: Option<u64>,
+    /// Exclusive upper bound on `at_ms`
+    pub to_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct AuditPage {
+    /// Matching entries, newest first
+    pub entries: Vec<AuditEntry>,
+    /// Pass as `before_seq` to get the next page; `None` on the last one
+    pub next_before_seq: Option<u64>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.caller.as_ref().is_none_or(|c| *c == entry.caller)
+            && self.method.as_ref().is_none_or(|m| *m == entry.method)
+            && self.document_id.as_ref().is_none_or(|d| Some(d) == entry.document_id.as_ref())
+            && self.from_ms.is_none_or(|from| entry.at_ms >= from)
+            && self.to_ms.is_none_or(|to| entry.at_ms < to)
+    }
+}
+
+impl SharedDocument {
+    /// Log a successful call of `method` by the current executor, dropping
+    /// the oldest entries past `Quotas::max_audit_entries`.
+    pub(crate) fn audit(&mut self, method: &str, document_id: Option<&str>, op_count: usize, detail: Option<String>) {
+        let version = document_id.and_then(|id| self.document(Some(id)).ok()).map(|d| d.version);
+        self.audit_log.push(AuditEntry {
+            seq: self.audit_log.last().map_or(1, |e| e.seq + 1),
+            method: method.to_string(),
+            caller: encode_executor_address().unwrap_or_default(),
+            at_ms: env::time_now(),
+            document_id: document_id.map(str::to_string),
+            version,
+            op_count: op_count as u32,
+            detail,
+        });
+        let excess = (self.audit_log.len() as u64).saturating_sub(self.quotas.max_audit_entries) as usize;
+        self.audit_log.drain(..excess);
+    }
+
+    /// Entries matching `filter` with a `seq` below `before_seq`, newest
+    /// first, at most `limit` of them.
+    pub(crate) fn audit_page(&self, filter: &AuditFilter, before_seq: Option<u64>, limit: usize) -> AuditPage {
+        let limit = limit.min(MAX_AUDIT_PAGE);
+        let mut matching = self
+            .audit_log
+            .iter()
+            .rev()
+            .filter(|e| before_seq.is_none_or(|before| e.seq < before))
+            .filter(|e| filter.matches(e));
+        let entries: Vec<AuditEntry> = matching.by_ref().take(limit).cloned().collect();
+        let next_before_seq = match (entries.last(), matching.next()) {
+            (Some(last), Some(_)) => Some(last.seq),
+            _ => None,
+        };
+        AuditPage { entries, next_before_seq }
+    }
+}
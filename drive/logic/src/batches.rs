@@ -0,0 +1,143 @@
+//! Client op batch ids and replay detection for `apply_ops`.
+//!
+//! Clients tag each batch with their own id and a sequence number. The state
+//! keeps, per executor and client, the highest sequence applied plus the
+//! versions assigned to the most recent batches, so a retried batch returns
+//! its original version instead of being applied a second time.
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+
+use crate::document::Document;
+
+/// How many applied batches per client are remembered for replays.
+pub(crate) const REPLAY_WINDOW: usize = 32;
+
+/// Client-generated identity of an `apply_ops` batch.
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct OpBatchId {
+    pub client_id: String,
+    /// Strictly increasing per client
+    pub seq: u64,
+}
+
+/// How `apply_ops` treats ops whose offsets fall outside the document.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub enum ApplyMode {
+    /// Reject the whole batch on the first out-of-range or empty op
+    #[default]
+    Strict,
+    /// Append inserts past the end, truncate deletes and drop empty ops
+    Clamp,
+}
+
+/// Unit `DocOp` indexes and lengths are counted in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub enum IndexUnit {
+    /// Unicode scalar values (Rust `char`s)
+    #[default]
+    Scalar,
+    /// UTF-16 code units, as counted by browser editors
+    Utf16,
+    /// Extended grapheme clusters, as perceived by users
+    Grapheme,
+}
+
+/// Optional knobs for `apply_ops`.
+#[derive(Debug, Clone, Default, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct ApplyOptions {
+    /// Makes retries of the same batch idempotent
+    pub batch_id: Option<OpBatchId>,
+    /// Defaults to `Strict`
+    pub mode: Option<ApplyMode>,
+    /// Defaults to `Scalar`
+    pub unit: Option<IndexUnit>,
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+pub(crate) struct AppliedBatch {
+    pub(crate) seq: u64,
+    pub(crate) version: u64,
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+pub(crate) struct ClientBatches {
+    pub(crate) executor: String,
+    pub(crate) client_id: String,
+    pub(crate) high_water: u64,
+    /// Most recent batches, oldest first, at most `REPLAY_WINDOW` long
+    pub(crate) recent: Vec<AppliedBatch>,
+}
+
+/// What to do with an incoming batch id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BatchCheck {
+    Fresh,
+    /// Already applied; carries the version it produced
+    Replayed(u64),
+    /// Below the high-water mark but never applied
+    OutOfOrder { high_water: u64 },
+    /// Older than every remembered batch, so whether it was applied is unknown
+    Expired,
+}
+
+impl Document {
+    fn client_batches(&self, executor: &str, client_id: &str) -> Option<&ClientBatches> {
+        self.client_batches.iter().find(|c| c.executor == executor && c.client_id == client_id)
+    }
+
+    pub(crate) fn check_batch(&self, executor: &str, batch: &OpBatchId) -> BatchCheck {
+        let Some(client) = self.client_batches(executor, &batch.client_id) else {
+            return BatchCheck::Fresh;
+        };
+        if batch.seq > client.high_water {
+            return BatchCheck::Fresh;
+        }
+        if let Some(applied) = client.recent.iter().find(|b| b.seq == batch.seq) {
+            return BatchCheck::Replayed(applied.version);
+        }
+        // Until the window fills up it holds every applied batch, and once
+        // full it holds every one since its oldest entry
+        let aged_out = client.recent.len() == REPLAY_WINDOW && client.recent.first().is_some_and(|b| batch.seq < b.seq);
+        if aged_out {
+            BatchCheck::Expired
+        } else {
+            BatchCheck::OutOfOrder { high_water: client.high_water }
+        }
+    }
+
+    /// Raise the client's high-water mark after a batch was applied. The
+    /// executor keeps at most `max_clients` client ids; the one that went
+    /// longest without a batch is forgotten first.
+    pub(crate) fn record_batch(&mut self, executor: &str, batch: &OpBatchId, version: u64, max_clients: u64) {
+        // most recently used last
+        let mut client = match self.client_batches.iter().position(|c| c.executor == executor && c.client_id == batch.client_id) {
+            Some(idx) => self.client_batches.remove(idx),
+            None => ClientBatches { executor: executor.to_string(), client_id: batch.client_id.clone(), high_water: 0, recent: Vec::new() },
+        };
+        client.high_water = client.high_water.max(batch.seq);
+        client.recent.push(AppliedBatch { seq: batch.seq, version });
+        if client.recent.len() > REPLAY_WINDOW {
+            let excess = client.recent.len() - REPLAY_WINDOW;
+            client.recent.drain(..excess);
+        }
+        self.client_batches.push(client);
+        let held = self.client_batches.iter().filter(|c| c.executor == executor).count() as u64;
+        if held > max_clients {
+            let oldest = self.client_batches.iter().position(|c| c.executor == executor);
+            if let Some(idx) = oldest {
+                self.client_batches.remove(idx);
+            }
+        }
+    }
+}
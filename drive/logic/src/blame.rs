@@ -0,0 +1,111 @@
+//! Authorship attribution of a document's text.
+//!
+//! Every item records who first inserted it, when and in which document
+//! version, and moves carry that over to the new slot, so blame is read
+//! straight off the visible elements. Deletions leave no author on the
+//! tombstone, so they are counted per author as they happen.
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+
+use crate::document::Document;
+
+/// Consecutive visible characters inserted by the same author in the same
+/// version.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct BlameRun {
+    pub text: String,
+    /// Scalar position of the run in the content
+    pub index: usize,
+    pub len: usize,
+    pub author: String,
+    pub created_ms: u64,
+    /// Version that inserted the text; 0 for text written before versions
+    /// were recorded per character
+    pub version: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct AuthorStats {
+    pub author: String,
+    /// Characters the author typed, whether or not they are still there
+    pub inserted: u64,
+    /// Characters the author deleted, whoever wrote them
+    pub deleted: u64,
+    /// Characters the author typed that are visible now
+    pub surviving: u64,
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+pub(crate) struct Deletions {
+    pub(crate) author: String,
+    pub(crate) count: u64,
+}
+
+impl Document {
+    pub(crate) fn count_deletions(&mut self, author: &str, count: usize) {
+        if count == 0 {
+            return;
+        }
+        match self.deletions.iter_mut().find(|d| d.author == author) {
+            Some(d) => d.count += count as u64,
+            None => self.deletions.push(Deletions { author: author.to_string(), count: count as u64 }),
+        }
+    }
+
+    pub(crate) fn blame_runs(&self) -> Vec<BlameRun> {
+        let mut runs: Vec<BlameRun> = Vec::new();
+        for (index, e) in self.elems.iter().filter(|e| e.visible).enumerate() {
+            let ch = char::from_u32(e.ch).unwrap_or('\u{FFFD}');
+            match runs.last_mut() {
+                Some(run) if run.author == e.editor && run.version == e.version && run.created_ms == e.created_ms => {
+                    run.text.push(ch);
+                    run.len += 1;
+                }
+                _ => runs.push(BlameRun {
+                    text: ch.to_string(),
+                    index,
+                    len: 1,
+                    author: e.editor.clone(),
+                    created_ms: e.created_ms,
+                    version: e.version,
+                }),
+            }
+        }
+        runs
+    }
+
+    /// Contribution counts of everyone who inserted or deleted text, most
+    /// surviving characters first.
+    pub(crate) fn author_stats(&self) -> Vec<AuthorStats> {
+        let mut stats: Vec<AuthorStats> = Vec::new();
+        // an item's first slot is the one whose uid it carries
+        for e in self.elems.iter().filter(|e| e.uid == e.item) {
+            stats_for(&mut stats, &e.editor).inserted += 1;
+        }
+        for e in self.elems.iter().filter(|e| e.visible) {
+            stats_for(&mut stats, &e.editor).surviving += 1;
+        }
+        for d in &self.deletions {
+            stats_for(&mut stats, &d.author).deleted += d.count;
+        }
+        stats.sort_by(|a, b| b.surviving.cmp(&a.surviving).then_with(|| a.author.cmp(&b.author)));
+        stats
+    }
+}
+
+fn stats_for<'a>(stats: &'a mut Vec<AuthorStats>, author: &str) -> &'a mut AuthorStats {
+    let pos = match stats.iter().position(|s| s.author == author) {
+        Some(pos) => pos,
+        None => {
+            stats.push(AuthorStats { author: author.to_string(), inserted: 0, deleted: 0, surviving: 0 });
+            stats.len() - 1
+        }
+    };
+    &mut stats[pos]
+}
@@ -0,0 +1,204 @@
+//! Content-addressed storage for small attachments kept inside the context.
+//!
+//! A blob is split into fixed-size chunks, each stored once under the
+//! SHA-256 of its bytes, so identical blobs and identical chunks of
+//! different blobs share storage. The blob itself is addressed by the
+//! SHA-256 of its whole content. Documents hold references to the blobs
+//! they use; a blob and the chunks only it used are dropped when the last
+//! document releases it.
+//!
+//! Only the blob records live in the root state. Chunk bytes are entries of
+//! a storage map, so loading and writing the root state does not carry every
+//! attachment with it. The bytes stored in chunks count against
+//! `Quotas::max_blob_bytes`.
+
+use std::fmt;
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+use calimero_storage::collections::UnorderedMap;
+use sha2::{Digest, Sha256};
+
+use crate::media::valid_mime;
+use crate::quotas::Quotas;
+use crate::AppError;
+
+/// Bytes per chunk; the last chunk of a blob may be shorter.
+pub(crate) const BLOB_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Largest blob accepted by `put_blob`.
+pub(crate) const MAX_BLOB_SIZE: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct BlobInfo {
+    /// `sha256:` followed by the hex digest of the content
+    pub hash: String,
+    pub size: u64,
+    pub mime: String,
+    pub chunk_size: u32,
+    pub chunk_count: u32,
+    /// Documents holding a reference, in the order they took it
+    pub documents: Vec<String>,
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+pub(crate) struct Blob {
+    hash: String,
+    size: u64,
+    mime: String,
+    // digests of the chunks, in order
+    chunks: Vec<String>,
+    documents: Vec<String>,
+}
+
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+pub(crate) struct Chunk {
+    data: Vec<u8>,
+    // blob chunk slots holding this chunk
+    uses: u32,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+pub(crate) struct BlobStore {
+    blobs: Vec<Blob>,
+    // chunks by digest, stored outside the root state
+    chunks: UnorderedMap<String, Chunk>,
+    // bytes of every stored chunk, each counted once
+    stored_bytes: u64,
+}
+
+impl Default for BlobStore {
+    fn default() -> Self {
+        BlobStore { blobs: Vec::new(), chunks: UnorderedMap::new(), stored_bytes: 0 }
+    }
+}
+
+impl fmt::Debug for BlobStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlobStore").field("blobs", &self.blobs).field("stored_bytes", &self.stored_bytes).finish_non_exhaustive()
+    }
+}
+
+fn digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// `sha256:` followed by the hex digest of `bytes`.
+pub(crate) fn content_address(bytes: &[u8]) -> String {
+    format!("sha256:{}", digest(bytes))
+}
+
+impl Blob {
+    fn info(&self) -> BlobInfo {
+        BlobInfo {
+            hash: self.hash.clone(),
+            size: self.size,
+            mime: self.mime.clone(),
+            chunk_size: BLOB_CHUNK_SIZE as u32,
+            chunk_count: self.chunks.len() as u32,
+            documents: self.documents.clone(),
+        }
+    }
+}
+
+impl BlobStore {
+    fn position(&self, hash: &str) -> Result<usize, AppError> {
+        self.blobs.iter().position(|b| b.hash == hash).ok_or_else(|| AppError::NotFound { kind: "blob", id: hash.to_string() })
+    }
+
+    /// Store `data` unless it is already stored and add a reference from
+    /// `document_id`. Returns the blob and whether the reference is new.
+    pub(crate) fn put(&mut self, data: &[u8], mime: String, document_id: &str, quotas: &Quotas) -> Result<(BlobInfo, bool), AppError> {
+        if data.is_empty() {
+            return Err(AppError::Empty { field: "data" });
+        }
+        if data.len() > MAX_BLOB_SIZE {
+            return Err(AppError::Quota { quota: "max_blob_size", limit: MAX_BLOB_SIZE as u64 });
+        }
+        if !valid_mime(&mime) {
+            return Err(AppError::InvalidMime { mime });
+        }
+        let hash = content_address(data);
+        let pos = match self.position(&hash) {
+            Ok(pos) => pos,
+            Err(_) => {
+                let pieces: Vec<(String, &[u8])> = data.chunks(BLOB_CHUNK_SIZE).map(|piece| (digest(piece), piece)).collect();
+                let mut added = 0;
+                for (at, (key, piece)) in pieces.iter().enumerate() {
+                    if !pieces[..at].iter().any(|(k, _)| k == key) && !self.chunks.contains(key)? {
+                        added += piece.len() as u64;
+                    }
+                }
+                quotas.check_blob_bytes(self.stored_bytes.saturating_add(added))?;
+
+                let mut chunks = Vec::new();
+                for (key, piece) in pieces {
+                    let chunk = match self.chunks.get(&key)? {
+                        Some(chunk) => Chunk { uses: chunk.uses + 1, ..chunk },
+                        None => Chunk { data: piece.to_vec(), uses: 1 },
+                    };
+                    self.chunks.insert(key.clone(), chunk)?;
+                    chunks.push(key);
+                }
+                self.stored_bytes += added;
+                self.blobs.push(Blob { hash, size: data.len() as u64, mime, chunks, documents: Vec::new() });
+                self.blobs.len() - 1
+            }
+        };
+        let blob = &mut self.blobs[pos];
+        let added = !blob.documents.iter().any(|d| d == document_id);
+        if added {
+            blob.documents.push(document_id.to_string());
+        }
+        Ok((blob.info(), added))
+    }
+
+    pub(crate) fn info(&self, hash: &str) -> Result<BlobInfo, AppError> {
+        Ok(self.blobs[self.position(hash)?].info())
+    }
+
+    pub(crate) fn chunk(&self, hash: &str, index: u32) -> Result<Vec<u8>, AppError> {
+        let blob = &self.blobs[self.position(hash)?];
+        let out_of_range = AppError::OutOfRange { index: index as usize, len: blob.chunks.len() };
+        let Some(key) = blob.chunks.get(index as usize) else {
+            return Err(out_of_range);
+        };
+        self.chunks.get(key)?.map(|c| c.data).ok_or(out_of_range)
+    }
+
+    /// Drop the reference of `document_id`, and the blob with it when it was
+    /// the last one. Returns how many references remain.
+    pub(crate) fn release(&mut self, hash: &str, document_id: &str) -> Result<u32, AppError> {
+        let pos = self.position(hash)?;
+        let blob = &mut self.blobs[pos];
+        let Some(at) = blob.documents.iter().position(|d| d == document_id) else {
+            return Err(AppError::BlobNotReferenced { hash: hash.to_string(), document_id: document_id.to_string() });
+        };
+        blob.documents.remove(at);
+        let remaining = blob.documents.len() as u32;
+        if remaining == 0 {
+            for key in self.blobs.remove(pos).chunks {
+                let Some(chunk) = self.chunks.get(&key)? else {
+                    continue;
+                };
+                if chunk.uses > 1 {
+                    self.chunks.insert(key, Chunk { uses: chunk.uses - 1, ..chunk })?;
+                } else {
+                    self.chunks.remove(&key)?;
+                    self.stored_bytes -= chunk.data.len() as u64;
+                }
+            }
+        }
+        Ok(remaining)
+    }
+
+    /// Blobs `document_id` holds a reference to.
+    pub(crate) fn referenced_by(&self, document_id: &str) -> Vec<BlobInfo> {
+        self.blobs.iter().filter(|b| b.documents.iter().any(|d| d == document_id)).map(Blob::info).collect()
+    }
+}
@@ -0,0 +1,428 @@
+//! Tombstone-based character sequence backing the shared document.
+//!
+//! Every inserted character becomes an `Element` with a uid minted from the
+//! hybrid logical clock; deletes only hide elements so concurrent edits can
+//! still refer to them.
+//!
+//! The sequence is an RGA: each element remembers the uid it was inserted
+//! after (its origin), and concurrent inserts after the same origin are
+//! ordered by descending uid. Edits are applied as identity-based `SeqOp`s,
+//! so replicas integrating the same ops in any order, with duplicates, end up
+//! with the same sequence.
+//!
+//! Moves follow Kleppmann's list move: an element is a slot in the sequence
+//! holding an item, and moving an item mints a fresh slot at the target that
+//! carries the same item id. The slot with the largest uid is the item's
+//! current position (last writer wins between concurrent moves), older slots
+//! stay behind as hidden placeholders. Deletes address the item, so an edit
+//! made concurrently to a moved character follows it to its new place.
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::batches::{ApplyMode, IndexUnit};
+use crate::env;
+use crate::units::{self, Offset};
+use crate::document::Document;
+use crate::{AppError, SharedDocument};
+
+/// Hybrid logical clock timestamp: the highest wall-clock reading seen so far
+/// plus a logical counter that orders events sharing the same reading.
+///
+/// Ordering is lexicographic on `(physical_ms, logical)`, so a node whose wall
+/// clock lags behind still produces timestamps after everything it observed.
+/// The counter stays below `1 << LOGICAL_BITS` so it fits in a uid; past
+/// that the clock moves on to the next millisecond.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct HlcTimestamp {
+    pub physical_ms: u64,
+    pub logical: u32,
+}
+
+impl HlcTimestamp {
+    /// Advance the clock for a local event and return the new timestamp.
+    pub(crate) fn tick(&mut self, wall_ms: u64) -> HlcTimestamp {
+        if wall_ms > self.physical_ms {
+            self.physical_ms = wall_ms;
+            self.logical = 0;
+        } else if self.logical < MAX_LOGICAL {
+            self.logical += 1;
+        } else {
+            self.physical_ms = self.physical_ms.wrapping_add(1);
+            self.logical = 0;
+        }
+        *self
+    }
+
+    /// Merge a timestamp observed from another replica so that the next
+    /// local tick is ordered after it.
+    pub(crate) fn observe(&mut self, remote: HlcTimestamp) {
+        if remote > *self {
+            *self = remote;
+        }
+    }
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub(crate) struct Element {
+    pub(crate) uid: u128,
+    // uid of the element this one was inserted after; `None` for the start
+    pub(crate) origin: Option<u128>,
+    // identity of the character; the uid of the slot it was first inserted in
+    pub(crate) item: u128,
+    // store as u32 for Borsh compatibility (char is not directly Borsh-serializable)
+    pub(crate) ch: u32,
+    pub(crate) visible: bool,
+    pub(crate) created_ms: u64,
+    pub(crate) editor: String,
+    // document version that first inserted the item; 0 if not recorded
+    pub(crate) version: u64,
+}
+
+/// Edit operation types accepted by the backend
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub enum DocOp {
+    Insert { index: usize, content: String },
+    Delete { index: usize, len: usize },
+    /// Replace `len` characters at `index` with `content` in one step.
+    Replace { index: usize, len: usize, content: String },
+    /// Move `len` characters at `from` to position `to`, counted in the
+    /// document before the move. The characters keep their identity.
+    Move { from: usize, len: usize, to: usize },
+}
+
+/// Identity-based edit on the element sequence. Local `DocOp`s are resolved
+/// into these against the current sequence.
+#[derive(Debug, Clone)]
+pub(crate) enum SeqOp {
+    /// Place `elem` after its origin, skipping concurrent inserts with larger
+    /// uids. A slot for an existing item moves that item.
+    Insert { elem: Element },
+    /// Tombstone the item held by the slot with this uid.
+    Remove { uid: u128 },
+    /// Revive the item held by the slot with this uid.
+    Restore { uid: u128 },
+}
+
+/// Outcome of integrating a `SeqOp`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Integration {
+    Applied,
+    /// Already integrated; nothing changed.
+    Duplicate,
+    /// The origin or target element has not been integrated yet.
+    MissingDependency,
+}
+
+/// Bits of a uid holding the logical counter of its timestamp.
+const LOGICAL_BITS: u32 = 16;
+const MAX_LOGICAL: u32 = (1 << LOGICAL_BITS) - 1;
+/// Bits of a uid holding the editor, as wide as before the clock existed.
+const EDITOR_BITS: u32 = 64 - LOGICAL_BITS;
+
+/// Pack an HLC timestamp and the editor into a uid: 64 bits of physical time,
+/// 16 bits of logical counter and 48 bits of the editor id as a tie-breaker
+/// between replicas ticking to the same timestamp. Editors that are not a
+/// bs58 key of at least 6 bytes contribute a hash of their address instead.
+pub(crate) fn make_uid(ts: HlcTimestamp, editor: &str) -> u128 {
+    let key = match bs58::decode(editor).into_vec() {
+        Ok(decoded) if decoded.len() * 8 >= EDITOR_BITS as usize => decoded,
+        _ => Sha256::digest(editor.as_bytes()).to_vec(),
+    };
+    let editor_part = key.iter().take((EDITOR_BITS / 8) as usize).fold(0u128, |acc, b| (acc << 8) | *b as u128);
+    ((ts.physical_ms as u128) << 64) | ((ts.logical as u128) << EDITOR_BITS) | editor_part
+}
+
+/// Recover the HLC timestamp a uid was minted at.
+pub(crate) fn uid_timestamp(uid: u128) -> HlcTimestamp {
+    HlcTimestamp { physical_ms: (uid >> 64) as u64, logical: (uid >> EDITOR_BITS) as u32 & MAX_LOGICAL }
+}
+
+/// How a `(index, len)` range resolved against the text.
+enum Range {
+    Fits(usize, usize),
+    Skip,
+    PastEnd,
+    SplitsCluster,
+}
+
+/// Resolve a range counted in `unit` to scalar positions. Both ends must sit
+/// on grapheme cluster boundaries unless counting scalars; `Clamp` widens a
+/// range to whole clusters and cuts it at the end of the text.
+fn resolve_range(text: &[char], index: usize, count: usize, unit: IndexUnit, strict: bool) -> Range {
+    let start = match units::resolve(text, index, unit, true) {
+        Offset::At(at) => at,
+        Offset::Inside { start, .. } if !strict => start,
+        Offset::PastEnd if !strict => return Range::Skip,
+        Offset::PastEnd => return Range::PastEnd,
+        Offset::Inside { .. } => return Range::SplitsCluster,
+    };
+    let end = match index.checked_add(count).map(|end| units::resolve(text, end, unit, true)) {
+        Some(Offset::At(at)) => at,
+        Some(Offset::Inside { end, .. }) if !strict => end,
+        Some(Offset::Inside { .. }) => return Range::SplitsCluster,
+        Some(Offset::PastEnd) | None if !strict => text.len(),
+        Some(Offset::PastEnd) | None => return Range::PastEnd,
+    };
+    Range::Fits(start, end.max(start))
+}
+
+/// Check a whole batch against the text it will see, op by op, before
+/// anything is mutated, converting offsets from `unit` to scalar positions.
+/// `Strict` rejects the batch on the first bad op; `Clamp` returns the ops
+/// rewritten to fit, widening ranges to whole grapheme clusters.
+pub(crate) fn validate_batch(ops: Vec<DocOp>, content: &str, mode: ApplyMode, unit: IndexUnit) -> Result<Vec<DocOp>, AppError> {
+    let strict = mode == ApplyMode::Strict;
+    let mut text: Vec<char> = content.chars().collect();
+    let mut out = Vec::with_capacity(ops.len());
+    for (op_index, op) in ops.into_iter().enumerate() {
+        let reject = |reason: &'static str, text: &[char]| AppError::InvalidOp { op_index, reason, doc_len: units::unit_len(text, unit) };
+        match op {
+            DocOp::Insert { index, content } => {
+                if content.is_empty() {
+                    if strict {
+                        return Err(reject("empty insert", &text));
+                    }
+                    continue;
+                }
+                let at = match units::resolve(&text, index, unit, false) {
+                    Offset::At(at) => at,
+                    Offset::PastEnd if !strict => text.len(),
+                    Offset::Inside { start, .. } if !strict => start,
+                    Offset::PastEnd => return Err(reject("insert index past end of document", &text)),
+                    Offset::Inside { .. } => return Err(reject("insert index splits a surrogate pair", &text)),
+                };
+                text.splice(at..at, content.chars());
+                out.push(DocOp::Insert { index: at, content });
+            }
+            DocOp::Delete { index, len: count } => {
+                if count == 0 {
+                    if strict {
+                        return Err(reject("empty delete", &text));
+                    }
+                    continue;
+                }
+                let (start, end) = match resolve_range(&text, index, count, unit, strict) {
+                    Range::Fits(start, end) if end > start => (start, end),
+                    Range::Fits(..) | Range::Skip => continue,
+                    Range::PastEnd => return Err(reject("delete range past end of document", &text)),
+                    Range::SplitsCluster => return Err(reject("delete splits a grapheme cluster", &text)),
+                };
+                text.drain(start..end);
+                out.push(DocOp::Delete { index: start, len: end - start });
+            }
+            DocOp::Replace { index, len: count, content } => {
+                if count == 0 && content.is_empty() {
+                    if strict {
+                        return Err(reject("empty replace", &text));
+                    }
+                    continue;
+                }
+                let (start, end) = match resolve_range(&text, index, count, unit, strict) {
+                    Range::Fits(start, end) => (start, end),
+                    Range::Skip => (text.len(), text.len()),
+                    Range::PastEnd => return Err(reject("replace range past end of document", &text)),
+                    Range::SplitsCluster => return Err(reject("replace splits a grapheme cluster", &text)),
+                };
+                text.splice(start..end, content.chars());
+                out.push(DocOp::Replace { index: start, len: end - start, content });
+            }
+            DocOp::Move { from, len: count, to } => {
+                if count == 0 {
+                    if strict {
+                        return Err(reject("empty move", &text));
+                    }
+                    continue;
+                }
+                let (start, end) = match resolve_range(&text, from, count, unit, strict) {
+                    Range::Fits(start, end) if end > start => (start, end),
+                    Range::Fits(..) | Range::Skip => continue,
+                    Range::PastEnd => return Err(reject("move range past end of document", &text)),
+                    Range::SplitsCluster => return Err(reject("move splits a grapheme cluster", &text)),
+                };
+                let to = match units::resolve(&text, to, unit, true) {
+                    Offset::At(at) => at,
+                    Offset::PastEnd if !strict => text.len(),
+                    Offset::Inside { start, .. } if !strict => start,
+                    Offset::PastEnd => return Err(reject("move target past end of document", &text)),
+                    Offset::Inside { .. } => return Err(reject("move target splits a grapheme cluster", &text)),
+                };
+                if to > start && to < end {
+                    if strict {
+                        return Err(reject("move target inside moved range", &text));
+                    }
+                    continue;
+                }
+                let moved: Vec<char> = text.drain(start..end).collect();
+                let at = if to >= end { to - moved.len() } else { to };
+                text.splice(at..at, moved);
+                out.push(DocOp::Move { from: start, len: end - start, to });
+            }
+        }
+    }
+    Ok(out)
+}
+
+impl SharedDocument {
+    /// Tick the clock for a new local event. Loading the state observes
+    /// every element, so the clock is already past all of them.
+    pub(crate) fn next_stamp(&mut self) -> HlcTimestamp {
+        self.clock.tick(env::time_now())
+    }
+
+    /// Merge the timestamp of every element into the clock. State synced
+    /// from other nodes can hold elements minted after the local clock.
+    pub(crate) fn observe_elements(&mut self) {
+        for elem in self.documents.iter().flat_map(|d| &d.elems) {
+            self.clock.observe(uid_timestamp(elem.uid));
+        }
+    }
+}
+
+/// Mint one element per char of `text` for document version `version`, each
+/// chained after the previous one and starting at `origin`.
+pub(crate) fn mint_elements(clock: &mut HlcTimestamp, text: &str, editor: &str, origin: Option<u128>, version: u64) -> Vec<Element> {
+    let mut out = Vec::new();
+    let mut origin = origin;
+    for ch in text.chars() {
+        let ts = clock.tick(env::time_now());
+        let uid = make_uid(ts, editor);
+        out.push(Element { uid, origin, item: uid, ch: ch as u32, visible: true, created_ms: ts.physical_ms, editor: editor.to_string(), version });
+        origin = Some(uid);
+    }
+    out
+}
+
+impl Document {
+    pub(crate) fn position_of(&self, uid: u128) -> Option<usize> {
+        self.elems.iter().position(|e| e.uid == uid)
+    }
+
+    /// Uid a local insert at visible `index` is anchored after: the visible
+    /// element just before it, or the last element when appending past the end.
+    fn origin_for_index(&self, index: usize) -> Option<u128> {
+        if index == 0 {
+            return None;
+        }
+        self.elems.iter().filter(|e| e.visible).nth(index - 1).or(self.elems.last()).map(|e| e.uid)
+    }
+
+    /// Integrate a local or replicated edit into the sequence.
+    pub(crate) fn integrate(&mut self, op: SeqOp) -> Integration {
+        match op {
+            SeqOp::Insert { elem } => {
+                if self.position_of(elem.uid).is_some() {
+                    return Integration::Duplicate;
+                }
+                let mut pos = match elem.origin {
+                    None => 0,
+                    Some(origin) => match self.position_of(origin) {
+                        Some(p) => p + 1,
+                        None => return Integration::MissingDependency,
+                    },
+                };
+                let mut elem = elem;
+                if elem.item != elem.uid {
+                    // a move: the newest slot of the item holds it, and a
+                    // deleted item stays deleted wherever it moves
+                    let Some(current) = self.current_slot(elem.item) else {
+                        return Integration::MissingDependency;
+                    };
+                    if elem.uid > self.elems[current].uid {
+                        elem.visible &= self.elems[current].visible;
+                        self.elems[current].visible = false;
+                    } else {
+                        elem.visible = false;
+                    }
+                }
+                // concurrent inserts after the same origin (and everything
+                // inserted after them) carry larger uids and stay in front
+                while pos < self.elems.len() && self.elems[pos].uid > elem.uid {
+                    pos += 1;
+                }
+                self.elems.insert(pos, elem);
+                Integration::Applied
+            }
+            SeqOp::Remove { uid } => match self.position_of(uid).and_then(|p| self.current_slot(self.elems[p].item)) {
+                None => Integration::MissingDependency,
+                Some(p) if !self.elems[p].visible => Integration::Duplicate,
+                Some(p) => {
+                    self.elems[p].visible = false;
+                    Integration::Applied
+                }
+            },
+            SeqOp::Restore { uid } => match self.position_of(uid).and_then(|p| self.current_slot(self.elems[p].item)) {
+                None => Integration::MissingDependency,
+                Some(p) if self.elems[p].visible => Integration::Duplicate,
+                Some(p) => {
+                    self.elems[p].visible = true;
+                    Integration::Applied
+                }
+            },
+        }
+    }
+
+    /// Position of the slot currently holding `item`: its newest slot.
+    pub(crate) fn current_slot(&self, item: u128) -> Option<usize> {
+        self.elems.iter().enumerate().filter(|(_, e)| e.item == item).max_by_key(|(_, e)| e.uid).map(|(p, _)| p)
+    }
+
+    /// Insert `text` before the visible element at `index`. Returns the new items.
+    pub(crate) fn insert_text(&mut self, clock: &mut HlcTimestamp, index: usize, text: &str, editor: &str) -> Vec<u128> {
+        let origin = self.origin_for_index(index);
+        let mut items = Vec::new();
+        for elem in mint_elements(clock, text, editor, origin, self.version.wrapping_add(1)) {
+            items.push(elem.item);
+            self.integrate(SeqOp::Insert { elem });
+        }
+        items
+    }
+
+    /// Tombstone `len` visible elements starting at visible `index`. Returns
+    /// the items hidden.
+    pub(crate) fn tombstone_range(&mut self, index: usize, len: usize) -> Vec<u128> {
+        let targets: Vec<u128> = self.elems.iter().filter(|e| e.visible).skip(index).take(len).map(|e| e.item).collect();
+        targets.into_iter().filter(|item| self.integrate(SeqOp::Remove { uid: *item }) == Integration::Applied).collect()
+    }
+
+    /// Move `len` visible elements at `from` to visible position `to`, counted
+    /// before the move. Each item gets a fresh slot at the target, chained
+    /// like an insert, and keeps its character and authorship. Returns the
+    /// `(left, new)` slot uid pairs.
+    pub(crate) fn move_range(&mut self, clock: &mut HlcTimestamp, from: usize, len: usize, to: usize, editor: &str) -> Vec<(u128, u128)> {
+        if to >= from && to <= from + len {
+            return Vec::new();
+        }
+        let moved: Vec<Element> = self.elems.iter().filter(|e| e.visible).skip(from).take(len).cloned().collect();
+        let mut origin = self.origin_for_index(to);
+        let mut slots = Vec::new();
+        for old in moved {
+            let uid = make_uid(clock.tick(env::time_now()), editor);
+            slots.push((old.uid, uid));
+            self.integrate(SeqOp::Insert { elem: Element { uid, origin, ..old } });
+            origin = Some(uid);
+        }
+        slots
+    }
+
+    /// Give `item` a fresh slot right after the slot `after`, which puts it
+    /// back where it was when `after` is a slot it left. Returns the new slot.
+    pub(crate) fn move_item_after(&mut self, clock: &mut HlcTimestamp, item: u128, after: u128, editor: &str) -> Option<u128> {
+        let current = self.elems[self.current_slot(item)?].clone();
+        let uid = make_uid(clock.tick(env::time_now()), editor);
+        self.integrate(SeqOp::Insert { elem: Element { uid, origin: Some(after), ..current } });
+        Some(uid)
+    }
+
+    /// Recompute the cached `content` string from the visible elements.
+    pub(crate) fn rebuild_content(&mut self) {
+        self.content = self.elems.iter().filter(|e| e.visible).map(|e| char::from_u32(e.ch).unwrap_or('\u{FFFD}')).collect();
+    }
+}
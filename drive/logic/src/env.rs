@@ -0,0 +1,84 @@
+//! Runtime environment used by the logic.
+//!
+//! In wasm builds every call goes straight to the Calimero host. Under
+//! `cargo test` the same functions are backed by a thread-local mock with a
+//! settable executor id, a controllable clock and a buffer capturing emitted
+//! events, so `SharedDocument` methods can run on a plain host.
+
+#[cfg(not(test))]
+pub fn executor_id() -> [u8; 32] {
+    calimero_sdk::env::executor_id()
+}
+
+#[cfg(not(test))]
+pub fn time_now() -> u64 {
+    calimero_storage::env::time_now()
+}
+
+#[cfg(not(test))]
+pub fn emit(event: crate::events::Event) {
+    calimero_sdk::app::emit!(event);
+}
+
+#[cfg(test)]
+pub use mock::{emit, executor_id, time_now};
+
+#[cfg(test)]
+pub mod mock {
+    use std::cell::RefCell;
+
+    use crate::events::Event;
+
+    struct MockEnv {
+        executor: [u8; 32],
+        now_ms: u64,
+        events: Vec<Event>,
+    }
+
+    thread_local! {
+        static ENV: RefCell<MockEnv> = const { RefCell::new(MockEnv { executor: [0; 32], now_ms: 0, events: Vec::new() }) };
+    }
+
+    pub fn executor_id() -> [u8; 32] {
+        ENV.with(|env| env.borrow().executor)
+    }
+
+    pub fn time_now() -> u64 {
+        ENV.with(|env| env.borrow().now_ms)
+    }
+
+    pub fn emit(event: Event) {
+        ENV.with(|env| env.borrow_mut().events.push(event));
+    }
+
+    /// Act as the executor with this id for subsequent calls.
+    pub fn set_executor(id: [u8; 32]) {
+        ENV.with(|env| env.borrow_mut().executor = id);
+    }
+
+    /// Set the wall clock reported by `time_now`.
+    pub fn set_time(now_ms: u64) {
+        ENV.with(|env| env.borrow_mut().now_ms = now_ms);
+    }
+
+    pub fn advance_time(delta_ms: u64) {
+        ENV.with(|env| {
+            let mut env = env.borrow_mut();
+            env.now_ms = env.now_ms.saturating_add(delta_ms);
+        });
+    }
+
+    /// Drain the events emitted since the last call.
+    pub fn take_events() -> Vec<Event> {
+        ENV.with(|env| std::mem::take(&mut env.borrow_mut().events))
+    }
+
+    /// Base58 address the logic records for an executor id.
+    pub fn address_of(id: [u8; 32]) -> String {
+        bs58::encode(&id).into_string()
+    }
+
+    pub fn reset() {
+        ENV.with(|env| *env.borrow_mut() = MockEnv { executor: [0; 32], now_ms: 0, events: Vec::new() });
+    }
+}
@@ -2,10 +2,49 @@
 // EVENTS MODULE - Domain events for decoupling
 // ============================================================================
 
+use crate::crdt::HlcTimestamp;
+use crate::publishing::DocStatus;
+
 #[calimero_sdk::app::event]
-pub enum Event<'a> {
-    /// Emitted when the shared document is created
-    DocumentCreated { content: &'a str, version: u64 },
-    /// Emitted when the shared document is updated
-    DocumentUpdated { content: &'a str, version: u64, editor: &'a str },
+pub enum Event {
+    /// Emitted when a document's content is set through `create_document`
+    DocumentCreated { document_id: String, content: String, version: u64, stamp: HlcTimestamp },
+    /// Emitted when `create_document` overwrites a document that had content;
+    /// `previous_hash` is the content address of the replaced text
+    DocumentReplaced {
+        document_id: String,
+        content: String,
+        version: u64,
+        previous_version: u64,
+        previous_hash: String,
+        editor: String,
+        stamp: HlcTimestamp,
+    },
+    /// Emitted when a document is updated
+    DocumentUpdated { document_id: String, content: String, version: u64, editor: String, stamp: HlcTimestamp },
+
+    UserPing {addr: String, last_seen_ms: u64, stamp: HlcTimestamp},
+
+    /// Emitted when a document moves through its lifecycle
+    StatusChanged { document_id: String, from: DocStatus, to: DocStatus, by: String },
+    /// Emitted when `publish` freezes a new snapshot for readers
+    DocumentPublished { document_id: String, version: u64, published_by: String },
+    /// Emitted when a published snapshot reaches its expiry and is taken down
+    PublicationExpired { document_id: String, version: u64 },
+
+    /// Emitted when an image or embed is added to the media registry
+    MediaRegistered { asset_id: String, uploader: String },
+    /// Emitted when an asset's alt text or caption changes
+    MediaUpdated { asset_id: String, alt: String, caption: String, version: u64, editor: String },
+
+    /// Emitted when a document takes a reference to a stored blob
+    BlobStored { hash: String, size: u64, document_id: String },
+    /// Emitted when a document drops its reference; `remaining` 0 means the blob is gone
+    BlobReleased { hash: String, document_id: String, remaining: u32 },
+
+    /// Emitted when an edit mentions a collaborator, who gets a notification
+    UserMentioned { recipient: String, document_id: String, mentioned_by: String, notification_id: u64, version: u64 },
+
+    /// Emitted when state stored in an older schema is rewritten in the current one
+    StateMigrated { from_schema: u16, to_schema: u16 },
 }
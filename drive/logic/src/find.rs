@@ -0,0 +1,144 @@
+//! Find and replace inside a single document.
+//!
+//! Patterns are compiled with the `regex` crate, whose matching time is
+//! linear in the content, so a hostile pattern cannot stall a call. Literal
+//! patterns are escaped and whole-word matching wraps the pattern in word
+//! boundaries; everything then runs through the same matcher. The matcher
+//! knows nothing of grapheme clusters, so matches that start or end inside
+//! one, such as a base letter without its combining accent, are skipped.
+//! Matches are reported both as visible offsets and as the item ids of their
+//! first and last character, which keep pointing at the same text while
+//! others edit.
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+use regex::{Regex, RegexBuilder};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::batches::IndexUnit;
+use crate::document::Document;
+use crate::units::unit_len;
+use crate::{AppError, DocOp};
+
+/// Upper bound on the compiled size of a pattern, in bytes.
+const PATTERN_SIZE_LIMIT: usize = 1 << 20;
+
+/// How `find_in_document` and `replace_all` interpret the pattern.
+#[derive(Debug, Clone, Default, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct FindOptions {
+    /// Treat the pattern as a regular expression; defaults to a literal
+    pub regex: Option<bool>,
+    /// Fold case when matching; defaults to case-sensitive
+    pub case_insensitive: Option<bool>,
+    /// Only match at word boundaries on both sides
+    pub whole_word: Option<bool>,
+    /// Unit reported offsets are counted in; defaults to `Scalar`
+    pub unit: Option<IndexUnit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct FindMatch {
+    /// Visible offset of the match, counted in `options.unit`
+    pub index: usize,
+    pub len: usize,
+    /// Item id of the first matched character, as 32 hex digits
+    pub start_anchor: String,
+    /// Item id of the last matched character, as 32 hex digits
+    pub end_anchor: String,
+}
+
+/// A non-empty match in scalar positions, with what it should become.
+pub(crate) struct Found {
+    pub(crate) index: usize,
+    pub(crate) len: usize,
+    pub(crate) replacement: String,
+}
+
+pub(crate) fn compile(pattern: &str, options: &FindOptions) -> Result<Regex, AppError> {
+    if pattern.is_empty() {
+        return Err(AppError::Empty { field: "pattern" });
+    }
+    let mut source = if options.regex.unwrap_or(false) { pattern.to_string() } else { regex::escape(pattern) };
+    if options.whole_word.unwrap_or(false) {
+        source = format!(r"\b(?:{source})\b");
+    }
+    RegexBuilder::new(&source)
+        .case_insensitive(options.case_insensitive.unwrap_or(false))
+        .size_limit(PATTERN_SIZE_LIMIT)
+        .build()
+        .map_err(|_| AppError::InvalidPattern { pattern: pattern.to_string() })
+}
+
+fn anchor(item: u128) -> String {
+    format!("{item:032x}")
+}
+
+impl Document {
+    /// Non-overlapping, non-empty matches of `re` in the content that start
+    /// and end on grapheme cluster boundaries, in order. With `replacement`,
+    /// capture groups are expanded into it for regex patterns; literal
+    /// patterns use it verbatim.
+    pub(crate) fn find_matches(&self, re: &Regex, replacement: Option<&str>, expand: bool) -> Vec<Found> {
+        let mut boundaries: Vec<usize> = self.content.grapheme_indices(true).map(|(i, _)| i).collect();
+        boundaries.push(self.content.len());
+        let on_boundary = |byte: usize| boundaries.binary_search(&byte).is_ok();
+
+        let mut found = Vec::new();
+        let mut chars_before = 0;
+        let mut scanned = 0;
+        for caps in re.captures_iter(&self.content) {
+            let m = caps.get(0).expect("group 0 always matches");
+            if m.is_empty() || !on_boundary(m.start()) || !on_boundary(m.end()) {
+                continue;
+            }
+            chars_before += self.content[scanned..m.start()].chars().count();
+            scanned = m.start();
+            let len = m.as_str().chars().count();
+            let replacement = match replacement {
+                Some(r) if expand => {
+                    let mut out = String::new();
+                    caps.expand(r, &mut out);
+                    out
+                }
+                Some(r) => r.to_string(),
+                None => String::new(),
+            };
+            found.push(Found { index: chars_before, len, replacement });
+        }
+        found
+    }
+
+    /// Report `found` in `unit`, with the items at both ends of each match.
+    pub(crate) fn match_views(&self, found: &[Found], unit: IndexUnit) -> Vec<FindMatch> {
+        let items: Vec<u128> = self.elems.iter().filter(|e| e.visible).map(|e| e.item).collect();
+        let text: Vec<char> = self.content.chars().collect();
+        found
+            .iter()
+            .map(|f| FindMatch {
+                index: unit_len(&text[..f.index], unit),
+                len: unit_len(&text[f.index..f.index + f.len], unit),
+                start_anchor: anchor(items[f.index]),
+                end_anchor: anchor(items[f.index + f.len - 1]),
+            })
+            .collect()
+    }
+}
+
+/// Ops that replace every match, last first so earlier offsets stay valid.
+pub(crate) fn replacement_ops(found: Vec<Found>) -> Vec<DocOp> {
+    found
+        .into_iter()
+        .rev()
+        .map(|f| {
+            if f.replacement.is_empty() {
+                DocOp::Delete { index: f.index, len: f.len }
+            } else {
+                DocOp::Replace { index: f.index, len: f.len, content: f.replacement }
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,152 @@
+//! Per-author undo and redo of `apply_ops` batches.
+//!
+//! A batch is recorded by identity rather than by index: the items it
+//! inserted or revived, the items it deleted and the slots it moved items
+//! between. Undoing inverts that record against the current sequence, so
+//! edits other authors made since stay intact: an item someone else already
+//! deleted is not touched again, and an item someone else moved since is not
+//! moved back. The inverse actually applied becomes the redo entry.
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::crdt::{HlcTimestamp, Integration, SeqOp};
+use crate::document::Document;
+use crate::quotas::Quotas;
+use crate::AppError;
+
+/// How many batches per author can be undone.
+pub(crate) const UNDO_DEPTH: usize = 50;
+
+#[derive(Debug, Clone, Default, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+pub(crate) struct EditRecord {
+    /// Items the batch made visible
+    pub(crate) shown: Vec<u128>,
+    /// Items the batch hid
+    pub(crate) hidden: Vec<u128>,
+    /// `(left, new)` slot uids of every item the batch moved
+    pub(crate) moved: Vec<(u128, u128)>,
+}
+
+impl EditRecord {
+    /// Drop items the batch both showed and hid, such as text typed and
+    /// deleted again in the same batch; undoing must not revive them.
+    pub(crate) fn normalized(mut self) -> Self {
+        let both: Vec<u128> = self.shown.iter().filter(|i| self.hidden.contains(i)).copied().collect();
+        self.shown.retain(|i| !both.contains(i));
+        self.hidden.retain(|i| !both.contains(i));
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.shown.is_empty() && self.hidden.is_empty() && self.moved.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+pub(crate) struct UndoStack {
+    pub(crate) executor: String,
+    /// Most recent last, at most `UNDO_DEPTH` long
+    pub(crate) undo: Vec<EditRecord>,
+    pub(crate) redo: Vec<EditRecord>,
+}
+
+impl Document {
+    fn undo_stack(&mut self, executor: &str) -> &mut UndoStack {
+        let idx = match self.undo_stacks.iter().position(|s| s.executor == executor) {
+            Some(idx) => idx,
+            None => {
+                self.undo_stacks.push(UndoStack { executor: executor.to_string(), undo: Vec::new(), redo: Vec::new() });
+                self.undo_stacks.len() - 1
+            }
+        };
+        &mut self.undo_stacks[idx]
+    }
+
+    /// Push a freshly applied batch; a new edit discards what could be redone.
+    pub(crate) fn record_edit(&mut self, executor: &str, edit: EditRecord) {
+        if edit.is_empty() {
+            return;
+        }
+        let stack = self.undo_stack(executor);
+        stack.redo.clear();
+        push_bounded(&mut stack.undo, edit);
+    }
+
+    /// Invert the executor's most recent batch (or undone batch, for `redo`)
+    /// and move the inverse onto the opposite stack. A batch touching text
+    /// someone else has leased, or reviving more text than `quotas` allow,
+    /// stays on its stack.
+    pub(crate) fn step_history(&mut self, clock: &mut HlcTimestamp, executor: &str, redo: bool, now: u64, quotas: &Quotas) -> Result<(), AppError> {
+        let stack = self.undo_stack(executor);
+        let from = if redo { &stack.redo } else { &stack.undo };
+        let Some(edit) = from.last().cloned() else {
+            return Err(if redo { AppError::NothingToRedo } else { AppError::NothingToUndo });
+        };
+        if let Some(lease) = self.lease_over_items(&self.edit_items(&edit), executor, now) {
+            return Err(self.leased(lease));
+        }
+        let len = self.content.chars().count();
+        quotas.check_growth(len, self.inverted_len(&edit, len))?;
+        let stack = self.undo_stack(executor);
+        let _ = if redo { stack.redo.pop() } else { stack.undo.pop() };
+        let inverse = self.invert(clock, &edit, executor);
+        let stack = self.undo_stack(executor);
+        push_bounded(if redo { &mut stack.undo } else { &mut stack.redo }, inverse);
+        Ok(())
+    }
+
+    fn edit_items(&self, edit: &EditRecord) -> Vec<u128> {
+        let moved = edit.moved.iter().filter_map(|(_, new)| self.position_of(*new)).map(|p| self.elems[p].item);
+        edit.shown.iter().chain(&edit.hidden).copied().chain(moved).collect()
+    }
+
+    /// Whether the slot currently holding `item` is visible.
+    fn item_visible(&self, item: u128) -> Option<bool> {
+        self.position_of(item).and_then(|p| self.current_slot(self.elems[p].item)).map(|p| self.elems[p].visible)
+    }
+
+    /// Length a document of `len` characters would have after `invert`,
+    /// which revives hidden items and hides shown ones that are not so yet.
+    fn inverted_len(&self, edit: &EditRecord, len: usize) -> usize {
+        let revived = edit.hidden.iter().filter(|i| self.item_visible(**i) == Some(false)).count();
+        let removed = edit.shown.iter().filter(|i| self.item_visible(**i) == Some(true)).count();
+        len + revived - removed
+    }
+
+    /// Apply the inverse of `edit` where the sequence still reflects it and
+    /// return a record of what changed.
+    fn invert(&mut self, clock: &mut HlcTimestamp, edit: &EditRecord, editor: &str) -> EditRecord {
+        let mut out = EditRecord::default();
+        for &(left, new) in edit.moved.iter().rev() {
+            let Some(p) = self.position_of(new) else { continue };
+            let item = self.elems[p].item;
+            if self.current_slot(item) != Some(p) {
+                continue;
+            }
+            if let Some(back) = self.move_item_after(clock, item, left, editor) {
+                out.moved.push((new, back));
+            }
+        }
+        for &item in &edit.shown {
+            if self.integrate(SeqOp::Remove { uid: item }) == Integration::Applied {
+                out.hidden.push(item);
+            }
+        }
+        for &item in &edit.hidden {
+            if self.integrate(SeqOp::Restore { uid: item }) == Integration::Applied {
+                out.shown.push(item);
+            }
+        }
+        out
+    }
+}
+
+fn push_bounded(stack: &mut Vec<EditRecord>, edit: EditRecord) {
+    stack.push(edit);
+    if stack.len() > UNDO_DEPTH {
+        let excess = stack.len() - UNDO_DEPTH;
+        stack.drain(..excess);
+    }
+}
@@ -0,0 +1,260 @@
+//! Exclusive edit leases on the whole document or on a range of it.
+//!
+//! A range lease is anchored on the items of its first and last character,
+//! so it follows the text as others edit around it and grows when the holder
+//! types inside it. Leases carry a TTL and lapse like presence entries:
+//! one acquired at `acquired_ms` is in force while `acquired_ms + ttl_ms >= now`.
+//! Expired leases are ignored everywhere and dropped on the next change.
+//! TTLs are capped at `MAX_LEASE_TTL_MS`, so a holder who goes away cannot
+//! lock the text for longer, and document owners can break any lease.
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+
+use crate::acl::Role;
+use crate::document::Document;
+use crate::{AppError, DocOp};
+
+/// Longest TTL a lease is taken or renewed for; longer requests are capped.
+pub(crate) const MAX_LEASE_TTL_MS: u64 = 10 * 60 * 1000;
+
+/// Visible range of a lease, in scalar positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct LeaseRange {
+    pub index: usize,
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct LeaseView {
+    pub id: u64,
+    pub holder: String,
+    /// `None` for a lease on the whole document
+    pub range: Option<LeaseRange>,
+    pub expires_ms: u64,
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+pub(crate) struct Lease {
+    pub(crate) id: u64,
+    pub(crate) holder: String,
+    /// Items of the first and last leased character; `None` for the whole document
+    pub(crate) anchors: Option<(u128, u128)>,
+    pub(crate) acquired_ms: u64,
+    pub(crate) ttl_ms: u64,
+}
+
+impl Lease {
+    fn active(&self, now: u64) -> bool {
+        self.acquired_ms.saturating_add(self.ttl_ms) >= now
+    }
+}
+
+#[derive(Debug, Clone, Default, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+pub(crate) struct LeaseTable {
+    pub(crate) next_id: u64,
+    pub(crate) leases: Vec<Lease>,
+}
+
+/// Sequence positions a lease covers, tombstones included.
+#[derive(Debug, Clone, Copy)]
+enum Span {
+    Document,
+    Slots(usize, usize),
+}
+
+impl Document {
+    fn span(&self, lease: &Lease) -> Span {
+        let Some((first, last)) = lease.anchors else {
+            return Span::Document;
+        };
+        match (self.current_slot(first), self.current_slot(last)) {
+            (Some(a), Some(b)) => Span::Slots(a.min(b), a.max(b)),
+            _ => Span::Document,
+        }
+    }
+
+    /// Visible `[start, end)` interval a span covers in the current content.
+    fn visible_interval(&self, span: Span) -> (usize, usize) {
+        match span {
+            Span::Document => (0, usize::MAX),
+            Span::Slots(a, b) => {
+                let start = self.elems[..a].iter().filter(|e| e.visible).count();
+                (start, start + self.elems[a..=b].iter().filter(|e| e.visible).count())
+            }
+        }
+    }
+
+    /// Visible range of a lease; `None` for the whole document.
+    fn visible_range(&self, lease: &Lease) -> Option<LeaseRange> {
+        match self.span(lease) {
+            Span::Document => None,
+            span => {
+                let (start, end) = self.visible_interval(span);
+                Some(LeaseRange { index: start, len: end - start })
+            }
+        }
+    }
+
+    /// The error for running into `lease`.
+    pub(crate) fn leased(&self, lease: &Lease) -> AppError {
+        AppError::Leased { lease_id: lease.id, holder: lease.holder.clone(), span: self.visible_range(lease) }
+    }
+
+    fn others_leases<'a>(&'a self, executor: &'a str, now: u64) -> impl Iterator<Item = &'a Lease> {
+        self.leases.leases.iter().filter(move |l| l.active(now) && l.holder != executor)
+    }
+
+    /// A lease in force held by anyone other than `executor`.
+    pub(crate) fn other_lease<'a>(&'a self, executor: &'a str, now: u64) -> Option<&'a Lease> {
+        self.others_leases(executor, now).next()
+    }
+
+    pub(crate) fn expire_leases(&mut self, now: u64) {
+        self.leases.leases.retain(|l| l.active(now));
+    }
+
+    /// Leases in force, with their ranges in the current content.
+    pub(crate) fn lease_views(&self, now: u64) -> Vec<LeaseView> {
+        self.leases
+            .leases
+            .iter()
+            .filter(|l| l.active(now))
+            .map(|l| LeaseView {
+                id: l.id,
+                holder: l.holder.clone(),
+                range: self.visible_range(l),
+                expires_ms: l.acquired_ms.saturating_add(l.ttl_ms),
+            })
+            .collect()
+    }
+
+    /// Take or renew a lease for `holder`, capping `ttl_ms`. Fails when it
+    /// would overlap a lease someone else holds.
+    pub(crate) fn take_lease(&mut self, holder: &str, range: Option<LeaseRange>, ttl_ms: u64, now: u64) -> Result<u64, AppError> {
+        self.expire_leases(now);
+        let ttl_ms = ttl_ms.min(MAX_LEASE_TTL_MS);
+        let anchors = match range {
+            None => None,
+            Some(LeaseRange { len: 0, .. }) => return Err(AppError::Empty { field: "range" }),
+            Some(LeaseRange { index, len }) => {
+                let mut visible = self.elems.iter().filter(|e| e.visible);
+                let first = visible.nth(index).map(|e| e.item);
+                let last = if len == 1 { first } else { visible.nth(len - 2).map(|e| e.item) };
+                match (first, last) {
+                    (Some(first), Some(last)) => Some((first, last)),
+                    _ => return Err(AppError::OutOfRange { index: index + len, len: self.elems.iter().filter(|e| e.visible).count() }),
+                }
+            }
+        };
+        let wanted = Lease { id: 0, holder: holder.to_string(), anchors, acquired_ms: now, ttl_ms };
+        let span = self.span(&wanted);
+        if let Some(lease) = self.others_leases(holder, now).find(|l| overlaps(span, self.span(l))) {
+            return Err(self.leased(lease));
+        }
+
+        if let Some(existing) = self.leases.leases.iter_mut().find(|l| l.holder == holder && l.anchors == anchors) {
+            existing.acquired_ms = now;
+            existing.ttl_ms = ttl_ms;
+            return Ok(existing.id);
+        }
+        self.leases.next_id += 1;
+        let id = self.leases.next_id;
+        self.leases.leases.push(Lease { id, ..wanted });
+        Ok(id)
+    }
+
+    /// Drop lease `id` for its holder, or break it for a document owner.
+    /// Returns the dropped lease.
+    pub(crate) fn drop_lease(&mut self, caller: &str, id: u64, now: u64) -> Result<Lease, AppError> {
+        self.expire_leases(now);
+        let Some(pos) = self.leases.leases.iter().position(|l| l.id == id) else {
+            return Err(AppError::NotFound { kind: "lease", id: id.to_string() });
+        };
+        if self.leases.leases[pos].holder != caller && self.role_of(caller) != Some(Role::Owner) {
+            return Err(self.leased(&self.leases.leases[pos]));
+        }
+        Ok(self.leases.leases.remove(pos))
+    }
+
+    /// Reject a validated batch from `executor` that touches text leased by
+    /// someone else. Ranges are tracked through the batch as earlier ops
+    /// shift them; inserting right at a range boundary does not touch it.
+    pub(crate) fn check_leases(&self, ops: &[DocOp], executor: &str, now: u64) -> Result<(), AppError> {
+        let mut ranges: Vec<(&Lease, usize, usize)> = Vec::new();
+        for lease in self.others_leases(executor, now) {
+            match self.span(lease) {
+                Span::Document => return Err(self.leased(lease)),
+                span => {
+                    let (start, end) = self.visible_interval(span);
+                    ranges.push((lease, start, end));
+                }
+            }
+        }
+        for op in ops {
+            for (lease, start, end) in ranges.iter_mut() {
+                let touched = match op {
+                    DocOp::Insert { index, content } => shift_insert(start, end, *index, content.chars().count()),
+                    DocOp::Delete { index, len } => shift_delete(start, end, *index, index + len),
+                    DocOp::Replace { index, len, content } => {
+                        shift_delete(start, end, *index, index + len) || shift_insert(start, end, *index, content.chars().count())
+                    }
+                    DocOp::Move { from, len, to } => {
+                        let at = if *to >= from + len { to - len } else { *to };
+                        shift_delete(start, end, *from, from + len) || shift_insert(start, end, at, *len)
+                    }
+                };
+                if touched {
+                    return Err(self.leased(lease));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A lease held by someone other than `executor` on text containing any of `items`.
+    pub(crate) fn lease_over_items<'a>(&'a self, items: &[u128], executor: &'a str, now: u64) -> Option<&'a Lease> {
+        self.others_leases(executor, now).find(|lease| match self.span(lease) {
+            Span::Document => true,
+            Span::Slots(a, b) => items.iter().filter_map(|i| self.current_slot(*i)).any(|p| p >= a && p <= b),
+        })
+    }
+}
+
+fn overlaps(a: Span, b: Span) -> bool {
+    match (a, b) {
+        (Span::Slots(a0, a1), Span::Slots(b0, b1)) => a0 <= b1 && b0 <= a1,
+        _ => true,
+    }
+}
+
+/// Shift `[start, end)` for an insert of `n` chars at `at`; true when the
+/// insert lands strictly inside it.
+fn shift_insert(start: &mut usize, end: &mut usize, at: usize, n: usize) -> bool {
+    if at > *start && at < *end {
+        return true;
+    }
+    if at <= *start {
+        *start += n;
+        *end += n;
+    }
+    false
+}
+
+/// Shift `[start, end)` for a delete of `[from, to)`; true when they overlap.
+fn shift_delete(start: &mut usize, end: &mut usize, from: usize, to: usize) -> bool {
+    if from < *end && to > *start {
+        return true;
+    }
+    if to <= *start {
+        *start -= to - from;
+        *end -= to - from;
+    }
+    false
+}
@@ -1,33 +1,72 @@
-//! Single-file ABI-visible crate root for the shared document application.
+//! Crate root for the shared document application.
 //!
-//! This file intentionally contains the state, events and logic inline so the
-//! build script (which reads `src/lib.rs`) can emit a complete ABI without
-//! needing to parse module files.
+//! The state and the `#[app::logic]` surface live here; documents, the CRDT
+//! sequence, search, presence tracking, events and schema migrations live in
+//! their own modules.
+//! `build.rs` inlines every `mod` declaration before handing the source to
+//! the ABI emitter, so types declared in modules still appear in the ABI.
 
 #![allow(clippy::len_without_is_empty)]
 
 use calimero_sdk::app;
 use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
-use calimero_storage::env;
 
 use calimero_sdk::serde::{Deserialize, Serialize};
+use calimero_storage::collections::StoreError;
 use thiserror::Error;
 
-use bs58;
+pub mod acl;
+pub mod audit;
+pub mod batches;
+pub mod blame;
+pub mod blobs;
+pub mod crdt;
+mod document;
+mod env;
+pub mod events;
+pub mod find;
+mod history;
+pub mod leases;
+pub mod media;
+pub mod mentions;
+mod migration;
+pub mod presence;
+pub mod publishing;
+pub mod quotas;
+pub mod search;
+pub mod templates;
+#[cfg(test)]
+mod simulator;
+#[cfg(test)]
+mod testing;
+#[cfg(test)]
+mod tests;
+mod units;
 
-// ============================================================================
-// EVENTS
-// ============================================================================
-
-#[calimero_sdk::app::event]
-pub enum Event {
-    /// Emitted when the shared document is created
-    DocumentCreated { content: String, version: u64 },
-    /// Emitted when the shared document is updated
-    DocumentUpdated { content: String, version: u64, editor: String },
+pub use acl::{Member, Role};
+pub use audit::{AuditEntry, AuditFilter, AuditPage};
+pub use batches::{ApplyMode, ApplyOptions, IndexUnit, OpBatchId};
+pub use blame::{AuthorStats, BlameRun};
+pub use blobs::BlobInfo;
+pub use crdt::{DocOp, HlcTimestamp};
+pub use events::Event;
+pub use find::{FindMatch, FindOptions};
+pub use leases::{LeaseRange, LeaseView};
+pub use media::{MediaAsset, NewMedia};
+pub use mentions::Notification;
+pub use presence::PresenceEntry;
+pub use publishing::{DocStatus, PublishedView};
+pub use quotas::Quotas;
+pub use search::{SearchHit, SearchMatch};
+pub use templates::{TemplateVar, TemplateView};
 
-    UserPing {addr: String, last_seen_ms: u64},
-}
+use batches::BatchCheck;
+use blobs::BlobStore;
+use document::{Document, DEFAULT_DOCUMENT};
+use history::EditRecord;
+use publishing::Scheduled;
+use search::SearchIndex;
+use templates::Template;
 
 // ============================================================================
 // TYPES
@@ -43,46 +82,82 @@ pub struct DocumentView {
     pub last_editor: Option<String>,
 }
 
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
-#[borsh(crate = "calimero_sdk::borsh")]
-#[serde(crate = "calimero_sdk::serde")]
-struct Element {
-    uid: u128,
-    ch: u32,
-    visible: bool,
-    created_ms: u64,
-    editor: String,
-}
-
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
-#[borsh(crate = "calimero_sdk::borsh")]
-#[serde(crate = "calimero_sdk::serde")]
-pub struct PresenceEntry {
-    pub address: String,
-    pub last_seen_ms: u64,
-    /// Optional payload provided by the client (can contain a short status or nonce)
-    pub payload: String,
-}
-
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
-#[borsh(crate = "calimero_sdk::borsh")]
-#[serde(crate = "calimero_sdk::serde")]
-pub enum DocOp {
-    Insert { index: usize, content: String },
-    Delete { index: usize, len: usize },
-}
-
-// Generic AppError for compatibility
-#[derive(Debug, Error, Serialize)]
+/// Errors returned by every method, serialised as `{ "kind": <variant>,
+/// "data": <fields> }`.
+#[derive(Debug, Error, PartialEq, Eq, Serialize)]
 #[serde(crate = "calimero_sdk::serde")]
 #[serde(tag = "kind", content = "data")]
 pub enum AppError {
-    #[error("not found: {0}")]
-    NotFound(String),
-    #[error("invalid input: {0}")]
-    Invalid(&'static str),
-    #[error("forbidden: {0}")]
-    Forbidden(&'static str),
+    #[error("{kind} {id} not found")]
+    NotFound { kind: &'static str, id: String },
+    /// A required string, list or byte argument is empty
+    #[error("{field} must not be empty")]
+    Empty { field: &'static str },
+    #[error("invalid find pattern {pattern:?}")]
+    InvalidPattern { pattern: String },
+    /// Not of the form `type/subtype`
+    #[error("invalid mime type {mime:?}")]
+    InvalidMime { mime: String },
+    #[error("forbidden: {required_role:?} role required")]
+    Forbidden { required_role: Role },
+    /// Only the identity that initialised the context may do this
+    #[error("forbidden: only the context owner {owner} may do this")]
+    NotContextOwner { owner: String },
+    /// The call names an address other than the caller's own
+    #[error("{address} is not the caller")]
+    NotCaller { address: String },
+    /// Someone else holds lease `lease_id` on the text; `span` is `None`
+    /// for a lease on the whole document
+    #[error("leased by {holder} (lease {lease_id})")]
+    Leased { lease_id: u64, holder: String, span: Option<LeaseRange> },
+    #[error("nothing to undo")]
+    NothingToUndo,
+    #[error("nothing to redo")]
+    NothingToRedo,
+    /// The change would leave the document without an owner
+    #[error("{address} is the last owner")]
+    LastOwner { address: String },
+    /// `overwrite` was set without `expected_version`
+    #[error("overwrite needs expected_version (current version {actual})")]
+    MissingExpectedVersion { actual: u64 },
+    #[error("document {document_id} does not reference blob {hash}")]
+    BlobNotReferenced { hash: String, document_id: String },
+    /// A `Quotas` limit set to zero
+    #[error("quota {quota} must be positive")]
+    InvalidQuota { quota: &'static str },
+    #[error("cannot move from {from:?} to {to:?}")]
+    InvalidTransition { from: DocStatus, to: DocStatus },
+    #[error("document {document_id} is not published")]
+    NotPublished { document_id: String },
+    #[error("expiry {expires_ms} is not after the publish time {publish_ms}")]
+    ExpiryBeforePublish { publish_ms: u64, expires_ms: u64 },
+    #[error("{kind} {id} already exists")]
+    Conflict { kind: &'static str, id: String },
+    /// `expected_version` no longer matches
+    #[error("version conflict: expected {expected}, found {actual}")]
+    VersionConflict { expected: u64, actual: u64 },
+    #[error("index {index} out of range for length {len}")]
+    OutOfRange { index: usize, len: usize },
+    /// A limit of `Quotas`, or a fixed one such as `max_blob_size`
+    #[error("quota {quota} exceeded: limit {limit}")]
+    Quota { quota: &'static str, limit: u64 },
+    #[error("invalid op {op_index}: {reason} (document length {doc_len})")]
+    InvalidOp { op_index: usize, reason: &'static str, doc_len: usize },
+    /// A batch id below the client's last one that was never applied
+    #[error("batch {seq} of {client_id} arrived after batch {high_water}")]
+    BatchOutOfOrder { client_id: String, seq: u64, high_water: u64 },
+    /// A batch id too old for the replay window to tell if it was applied
+    #[error("batch {seq} of {client_id} is older than the replay window")]
+    BatchExpired { client_id: String, seq: u64 },
+    /// The host storage behind a collection failed
+    #[error("storage error: {message}")]
+    Storage { message: String },
+}
+
+impl From<StoreError> for AppError {
+    fn from(err: StoreError) -> Self {
+        AppError::Storage { message: err.to_string() }
+    }
 }
 
 pub use AppError as GameError;
@@ -91,39 +166,206 @@ pub use AppError as GameError;
 // STATE
 // ============================================================================
 
+/// Persisted through the versioned envelope in `migration`, which also
+/// upgrades blobs written by older builds.
 #[app::state(emits = Event)]
-#[derive(Debug, BorshSerialize, BorshDeserialize)]
-#[borsh(crate = "calimero_sdk::borsh")]
+#[derive(Debug)]
 pub struct SharedDocument {
-    content: String,
-    version: u64,
-    updated_ms: u64,
-    last_editor: Option<String>,
-    elems: Vec<Element>,
+    // documents of the drive; the default one always exists
+    documents: Vec<Document>,
     // presence entries for active users
     presence_entries: Vec<PresenceEntry>,
-    id_nonce: u64,
+    // hybrid logical clock used for uids, `updated_ms` and event stamps
+    clock: HlcTimestamp,
+    // inverted index over the content of every document
+    search_index: SearchIndex,
+    // documents registered as templates
+    templates: Vec<Template>,
+    // images and embeds the documents can refer to
+    media: Vec<MediaAsset>,
+    // attachments stored in the context, by content hash
+    blobs: BlobStore,
+    // mentions of collaborators, per recipient
+    notifications: Vec<Notification>,
+    // every successful mutating call, oldest first; append-only
+    audit_log: Vec<AuditEntry>,
+    // size limits for everything above, set by `owner`
+    quotas: Quotas,
+    // whoever initialised the context; `None` for contexts from before schema 3
+    owner: Option<String>,
+    // schema the state was decoded from; not persisted
+    loaded_schema: u16,
+}
+
+impl BorshSerialize for SharedDocument {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        migration::write_state(self, writer)
+    }
+}
+
+impl BorshDeserialize for SharedDocument {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        migration::read_state(reader)
+    }
 }
 
 // ============================================================================
 // IMPLEMENTATION
 // ============================================================================
 
-fn encode_executor_address() -> app::Result<String> {
-    let id = calimero_sdk::env::executor_id();
+fn encode_executor_address() -> app::Result<String, AppError> {
+    let id = env::executor_id();
     Ok(bs58::encode(&id).into_string())
 }
 
-fn make_uid(now: u64, nonce: u64, editor: &str) -> u128 {
-    let mut hash_part: u128 = 0;
-    if let Ok(decoded) = bs58::decode(editor).into_vec() {
-        for (i, b) in decoded.iter().take(16).enumerate() {
-            hash_part |= (*b as u128) << ((15 - i) * 8);
+impl SharedDocument {
+    /// Shared body of `undo` and `redo`.
+    fn step_history(&mut self, document_id: Option<String>, redo: bool) -> app::Result<u64, AppError> {
+        let editor_addr = encode_executor_address()?;
+        let idx = self.document_index(document_id.as_deref())?;
+        self.settle(idx);
+        let stamp = self.next_stamp();
+        self.documents[idx].step_history(&mut self.clock, &editor_addr, redo, env::time_now(), &self.quotas)?;
+        let version = self.commit_edit(idx, editor_addr, stamp);
+        let id = self.documents[idx].id.clone();
+        self.audit(if redo { "redo" } else { "undo" }, Some(&id), 0, None);
+        Ok(version)
+    }
+
+    /// Apply the due schedules of every document. Returns how many took
+    /// effect.
+    fn settle_all(&mut self) -> u32 {
+        let mut applied = 0;
+        for idx in 0..self.documents.len() {
+            applied += self.settle(idx);
+        }
+        applied
+    }
+
+    /// Apply due schedules of the document at `idx` and announce them.
+    /// Returns how many took effect.
+    fn settle(&mut self, idx: usize) -> u32 {
+        let doc = &mut self.documents[idx];
+        let changes = doc.advance_schedule(env::time_now());
+        let applied = changes.len() as u32;
+        for change in changes {
+            let document_id = doc.id.clone();
+            match change {
+                Scheduled::Published { from, snapshot } => {
+                    if from != DocStatus::Published {
+                        let by = snapshot.published_by.clone();
+                        env::emit(Event::StatusChanged { document_id: document_id.clone(), from, to: DocStatus::Published, by });
+                    }
+                    env::emit(Event::DocumentPublished { document_id, version: snapshot.version, published_by: snapshot.published_by });
+                }
+                Scheduled::Expired { version, by, archived } => {
+                    if archived {
+                        env::emit(Event::StatusChanged { document_id: document_id.clone(), from: DocStatus::Published, to: DocStatus::Archived, by });
+                    }
+                    env::emit(Event::PublicationExpired { document_id, version });
+                }
+            }
+        }
+        applied
+    }
+
+    /// Replace the whole sequence of the document at `idx` with `content`,
+    /// authored by `editor_addr`, as a new version, notifying anyone the new
+    /// text mentions.
+    fn write_content(&mut self, idx: usize, content: String, editor_addr: &str) -> u64 {
+        self.settle(idx);
+        let stamp = self.next_stamp();
+        let doc = &mut self.documents[idx];
+        let previous = (doc.version > 0).then(|| (doc.version, blobs::content_address(doc.content.as_bytes())));
+        self.search_index.update(&doc.id, &doc.content, &content);
+        doc.version = doc.version.wrapping_add(1);
+        let old = std::mem::replace(&mut doc.content, content.clone());
+        doc.updated_ms = stamp.physical_ms;
+        doc.last_editor = Some(editor_addr.to_string());
+
+        doc.elems = crdt::mint_elements(&mut self.clock, &content, editor_addr, None, doc.version);
+        // recorded edits, lease anchors and contributions refer to the
+        // replaced sequence
+        doc.undo_stacks.clear();
+        doc.leases.leases.clear();
+        doc.deletions.clear();
+        doc.claim(editor_addr);
+
+        let (document_id, version) = (doc.id.clone(), doc.version);
+        match previous {
+            None => env::emit(Event::DocumentCreated { document_id, content, version, stamp }),
+            Some((previous_version, previous_hash)) => env::emit(Event::DocumentReplaced {
+                document_id,
+                content,
+                version,
+                previous_version,
+                previous_hash,
+                editor: editor_addr.to_string(),
+                stamp,
+            }),
+        }
+        self.notify_mentions(idx, &old, editor_addr, stamp.physical_ms);
+        version
+    }
+
+    /// Apply ops already validated against the document at `idx` and record
+    /// them as one undoable edit of `editor_addr`.
+    fn apply_validated(&mut self, idx: usize, ops: Vec<DocOp>, editor_addr: &str) {
+        let doc = &mut self.documents[idx];
+        let clock = &mut self.clock;
+        let mut edit = EditRecord::default();
+        for op in ops.into_iter() {
+            match op {
+                DocOp::Insert { index, content } => edit.shown.extend(doc.insert_text(clock, index, &content, editor_addr)),
+                DocOp::Delete { index, len } => edit.hidden.extend(doc.tombstone_range(index, len)),
+                DocOp::Replace { index, len, content } => {
+                    edit.hidden.extend(doc.tombstone_range(index, len));
+                    edit.shown.extend(doc.insert_text(clock, index, &content, editor_addr));
+                }
+                DocOp::Move { from, len, to } => edit.moved.extend(doc.move_range(clock, from, len, to, editor_addr)),
+            }
+        }
+        let edit = edit.normalized();
+        doc.count_deletions(editor_addr, edit.hidden.len());
+        doc.record_edit(editor_addr, edit);
+    }
+
+    /// Publish an edit to the sequence of the document at `idx` as a new
+    /// version, keeping the search index in step and notifying anyone the
+    /// edit mentions.
+    fn commit_edit(&mut self, idx: usize, editor_addr: String, stamp: HlcTimestamp) -> u64 {
+        let doc = &mut self.documents[idx];
+        let old = std::mem::take(&mut doc.content);
+        doc.rebuild_content();
+        self.search_index.update(&doc.id, &old, &doc.content);
+        doc.version = doc.version.wrapping_add(1);
+        doc.updated_ms = stamp.physical_ms;
+        doc.last_editor = Some(editor_addr.clone());
+        env::emit(Event::DocumentUpdated {
+            document_id: doc.id.clone(),
+            content: doc.content.clone(),
+            version: doc.version,
+            editor: editor_addr.clone(),
+            stamp,
+        });
+        let version = doc.version;
+        self.notify_mentions(idx, &old, &editor_addr, stamp.physical_ms);
+        version
+    }
+
+    /// Record the mentions the document at `idx` gained over `old` and emit
+    /// an event for each notification.
+    fn notify_mentions(&mut self, idx: usize, old: &str, editor_addr: &str, now: u64) {
+        for n in self.record_mentions(idx, old, editor_addr, now) {
+            env::emit(Event::UserMentioned {
+                recipient: n.recipient,
+                document_id: n.document_id,
+                mentioned_by: n.mentioned_by,
+                notification_id: n.id,
+                version: n.version,
+            });
         }
     }
-    let high = (now as u128) << 64;
-    let mid = (nonce as u128) << 48;
-    high | mid | (hash_part & 0xffffffffffff)
 }
 
 #[app::logic]
@@ -131,124 +373,652 @@ impl SharedDocument {
     #[app::init]
     pub fn init() -> SharedDocument {
         SharedDocument {
-            content: String::new(),
-            version: 0,
-            updated_ms: env::time_now(),
-            last_editor: None,
-            elems: Vec::new(),
+            documents: vec![Document::new(DEFAULT_DOCUMENT, env::time_now())],
             presence_entries: Vec::new(),
-            id_nonce: 0,
+            clock: HlcTimestamp::default(),
+            search_index: SearchIndex::default(),
+            templates: Vec::new(),
+            media: Vec::new(),
+            blobs: BlobStore::default(),
+            notifications: Vec::new(),
+            audit_log: Vec::new(),
+            quotas: Quotas::default(),
+            owner: encode_executor_address().ok(),
+            loaded_schema: migration::CURRENT_SCHEMA,
+        }
+    }
+
+    /// Create document `document_id` (the default document when `None`)
+    /// with `content`. A document that already has content is only replaced
+    /// with `overwrite` set, by an owner, at `expected_version`; its history
+    /// is discarded.
+    pub fn create_document(
+        &mut self,
+        content: String,
+        expected_version: Option<u64>,
+        overwrite: Option<bool>,
+        document_id: Option<String>,
+    ) -> app::Result<u64, AppError> {
+        if content.is_empty() {
+            return Err(GameError::Empty { field: "content" });
+        }
+        self.quotas.check_content(&content)?;
+        let id = document_id.unwrap_or_else(|| DEFAULT_DOCUMENT.to_string());
+        if id.is_empty() {
+            return Err(GameError::Empty { field: "document_id" });
+        }
+        let editor_addr = encode_executor_address()?;
+        let idx = match self.document_index(Some(&id)) {
+            Ok(idx) => idx,
+            Err(_) => {
+                self.quotas.check_documents(self.documents.len())?;
+                self.documents.push(Document::new(&id, env::time_now()));
+                self.documents.len() - 1
+            }
+        };
+        let doc = &self.documents[idx];
+        if doc.version > 0 {
+            if !overwrite.unwrap_or(false) {
+                return Err(GameError::Conflict { kind: "document", id });
+            }
+            match expected_version {
+                None => return Err(GameError::MissingExpectedVersion { actual: doc.version }),
+                Some(ev) if ev != doc.version => return Err(GameError::VersionConflict { expected: ev, actual: doc.version }),
+                Some(_) => {}
+            }
+            doc.require_role(&editor_addr, Role::Owner)?;
         }
+        if let Some(lease) = doc.other_lease(&editor_addr, env::time_now()) {
+            return Err(doc.leased(lease));
+        }
+        let previous = doc.version;
+        let version = self.write_content(idx, content, &editor_addr);
+        let detail = (previous > 0).then(|| format!("replaced version {previous}"));
+        self.audit("create_document", Some(&id), 0, detail);
+        Ok(version)
     }
 
-    pub fn create_document(&mut self, content: String) -> app::Result<u64> {
+    /// Start a new document from template `template_id`, with `vars`
+    /// filling its placeholders. The text gets fresh element ids and the
+    /// caller as author. Returns the id of the new document, which is
+    /// `document_id` or, when `None`, the first free `<template_id>-<n>`.
+    pub fn instantiate_template(&mut self, template_id: String, vars: Vec<TemplateVar>, document_id: Option<String>) -> app::Result<String, AppError> {
+        let editor_addr = encode_executor_address()?;
+        self.template(&template_id)?;
+        let id = self.new_document_id(document_id, &template_id)?;
+        let mut vars = vars;
+        vars.push(TemplateVar { name: "author".to_string(), value: editor_addr.clone() });
+        vars.push(TemplateVar { name: "date".to_string(), value: templates::iso_date(env::time_now()) });
+        let content = templates::fill(&self.document(Some(&template_id))?.content, &vars);
         if content.is_empty() {
-            app::bail!(GameError::Invalid("empty document content"));
+            return Err(GameError::Empty { field: "content" });
         }
-        self.version = self.version.wrapping_add(1);
-        self.content = content.clone();
-        self.updated_ms = env::time_now();
+        self.quotas.check_content(&content)?;
+        self.quotas.check_documents(self.documents.len())?;
+        self.documents.push(Document::new(&id, env::time_now()));
+        self.write_content(self.documents.len() - 1, content, &editor_addr);
+        self.audit("instantiate_template", Some(&id), 0, None);
+        Ok(id)
+    }
+
+    /// Copy the current text of `document_id` into a new document with fresh
+    /// element ids and the caller as author. Returns the id of the copy,
+    /// which is `new_document_id` or the first free `<document_id>-<n>`.
+    pub fn duplicate_document(&mut self, document_id: String, new_document_id: Option<String>) -> app::Result<String, AppError> {
         let editor_addr = encode_executor_address()?;
-        self.last_editor = Some(editor_addr.clone());
+        let content = self.document(Some(&document_id))?.content.clone();
+        if content.is_empty() {
+            return Err(GameError::Empty { field: "content" });
+        }
+        self.quotas.check_content(&content)?;
+        let id = self.new_document_id(new_document_id, &document_id)?;
+        self.quotas.check_documents(self.documents.len())?;
+        self.documents.push(Document::new(&id, env::time_now()));
+        self.write_content(self.documents.len() - 1, content, &editor_addr);
+        self.audit("duplicate_document", Some(&id), 0, None);
+        Ok(id)
+    }
 
-        self.elems.clear();
-        self.id_nonce = 0;
-        let now = self.updated_ms;
-        for ch in content.chars() {
-            let uid = make_uid(now, self.id_nonce, &editor_addr);
-            self.id_nonce = self.id_nonce.wrapping_add(1);
-            self.elems.push(Element { uid, ch: ch as u32, visible: true, created_ms: now, editor: editor_addr.clone() });
+    /// Register `document_id` as a template named `name`, rename it if it
+    /// already is one, or unregister it when `name` is `None`.
+    pub fn mark_template(&mut self, name: Option<String>, document_id: Option<String>) -> app::Result<(), AppError> {
+        let id = self.document(document_id.as_deref())?.id.clone();
+        let existing = self.templates.iter().position(|t| t.document_id == id);
+        match (name, existing) {
+            (Some(name), _) if name.is_empty() => return Err(GameError::Empty { field: "name" }),
+            (Some(name), Some(pos)) if self.templates[pos].name == name => return Ok(()),
+            (Some(name), Some(pos)) => self.templates[pos].name = name,
+            (Some(name), None) => self.templates.push(Template { document_id: id.clone(), name }),
+            (None, Some(pos)) => {
+                self.templates.remove(pos);
+            }
+            (None, None) => return Ok(()),
         }
+        self.audit("mark_template", Some(&id), 0, None);
+        Ok(())
+    }
+
+    /// Registered templates with the placeholders each one uses.
+    pub fn list_templates(&self) -> app::Result<Vec<TemplateView>, AppError> {
+        Ok(self.template_views())
+    }
 
-    app::emit!(Event::DocumentCreated { content: self.content.clone(), version: self.version });
-        Ok(self.version)
+    /// Register an uploaded image or embed. Registering the same URL with
+    /// the checksum already on record for it returns the existing asset
+    /// instead.
+    pub fn register_media(&mut self, media: NewMedia) -> app::Result<MediaAsset, AppError> {
+        media.validate()?;
+        if let Some(existing) = self.media.iter().find(|m| m.url == media.url && m.checksum == media.checksum) {
+            return Ok(existing.clone());
+        }
+        let uploader = encode_executor_address()?;
+        let asset = MediaAsset {
+            asset_id: self.new_media_id(),
+            url: media.url,
+            mime: media.mime,
+            width: media.width,
+            height: media.height,
+            alt: media.alt.unwrap_or_default(),
+            caption: media.caption.unwrap_or_default(),
+            uploader: uploader.clone(),
+            checksum: media.checksum,
+            created_ms: env::time_now(),
+            version: 0,
+            last_editor: uploader.clone(),
+        };
+        self.media.push(asset.clone());
+        env::emit(Event::MediaRegistered { asset_id: asset.asset_id.clone(), uploader });
+        self.audit("register_media", None, 0, Some(asset.asset_id.clone()));
+        Ok(asset)
     }
 
-    pub fn apply_ops(&mut self, ops: Vec<DocOp>, expected_version: Option<u64>) -> app::Result<u64> {
-        if let Some(ev) = expected_version { if ev != self.version { app::bail!(GameError::Invalid("version mismatch")); } }
+    /// Change the alt text and/or caption of an asset, for every document
+    /// showing it. Returns the asset's new version.
+    pub fn update_media(&mut self, asset_id: String, alt: Option<String>, caption: Option<String>, expected_version: Option<u64>) -> app::Result<u64, AppError> {
         let editor_addr = encode_executor_address()?;
-        let now = env::time_now();
+        let idx = self.media_index(&asset_id)?;
+        let asset = &mut self.media[idx];
+        if let Some(ev) = expected_version { if ev != asset.version { return Err(GameError::VersionConflict { expected: ev, actual: asset.version }); } }
+        if alt.is_none() && caption.is_none() {
+            return Ok(asset.version);
+        }
+        if let Some(alt) = alt {
+            asset.alt = alt;
+        }
+        if let Some(caption) = caption {
+            asset.caption = caption;
+        }
+        asset.version = asset.version.wrapping_add(1);
+        asset.last_editor = editor_addr.clone();
+        let version = asset.version;
+        env::emit(Event::MediaUpdated { asset_id: asset_id.clone(), alt: asset.alt.clone(), caption: asset.caption.clone(), version, editor: editor_addr });
+        self.audit("update_media", None, 0, Some(asset_id));
+        Ok(version)
+    }
 
-        for op in ops.into_iter() {
-            match op {
-                DocOp::Insert { index, content } => {
-                    let mut insert_elems = Vec::new();
-                    for ch in content.chars() {
-                        let uid = make_uid(now, self.id_nonce, &editor_addr);
-                        self.id_nonce = self.id_nonce.wrapping_add(1);
-                        insert_elems.push(Element { uid, ch: ch as u32, visible: true, created_ms: now, editor: editor_addr.clone() });
-                    }
-                    let vis_positions: Vec<usize> = self.elems.iter().enumerate().filter(|(_, e)| e.visible).map(|(i, _)| i).collect();
-                    let insert_pos = if index == 0 { 0 } else { vis_positions.get(index.wrapping_sub(1)).map(|p| p+1).unwrap_or(self.elems.len()) };
-                    self.elems.splice(insert_pos..insert_pos, insert_elems);
-                }
-                DocOp::Delete { index, len } => {
-                    let mut remaining = len;
-                    let mut cur_vis = 0usize;
-                    for e in self.elems.iter_mut() {
-                        if !e.visible { continue; }
-                        if cur_vis >= index && remaining > 0 { e.visible = false; remaining = remaining.saturating_sub(1); }
-                        cur_vis = cur_vis.saturating_add(1);
-                        if remaining == 0 { break; }
-                    }
+    pub fn get_media(&self, asset_id: String) -> app::Result<MediaAsset, AppError> {
+        Ok(self.media[self.media_index(&asset_id)?].clone())
+    }
+
+    /// Registered assets, or with `document_id` only those the document
+    /// refers to, in the order it first does.
+    pub fn list_media(&self, document_id: Option<String>) -> app::Result<Vec<MediaAsset>, AppError> {
+        let Some(id) = document_id else {
+            return Ok(self.media.clone());
+        };
+        let doc = self.document(Some(&id))?;
+        Ok(media::references(&doc.content)
+            .into_iter()
+            .filter_map(|asset_id| self.media.iter().find(|m| m.asset_id == asset_id).cloned())
+            .collect())
+    }
+
+    /// Registered assets no document refers to any more.
+    pub fn find_unreferenced_media(&self) -> app::Result<Vec<MediaAsset>, AppError> {
+        Ok(self.media.iter().filter(|m| !self.media_referenced(&m.asset_id)).cloned().collect())
+    }
+
+    /// Store a small attachment in the context for the document and return
+    /// its content address. Storing content that is already there only adds
+    /// the document's reference.
+    pub fn put_blob(&mut self, data: Vec<u8>, mime: String, document_id: Option<String>) -> app::Result<BlobInfo, AppError> {
+        let caller = encode_executor_address()?;
+        let doc = self.document(document_id.as_deref())?;
+        doc.require_role(&caller, Role::Editor)?;
+        let id = doc.id.clone();
+        let (info, added) = self.blobs.put(&data, mime, &id, &self.quotas)?;
+        if added {
+            env::emit(Event::BlobStored { hash: info.hash.clone(), size: info.size, document_id: id.clone() });
+        }
+        self.audit("put_blob", Some(&id), 0, Some(info.hash.clone()));
+        Ok(info)
+    }
+
+    pub fn get_blob(&self, hash: String) -> app::Result<BlobInfo, AppError> {
+        self.blobs.info(&hash)
+    }
+
+    /// Bytes of chunk `index` of a blob; see `BlobInfo::chunk_count`.
+    pub fn get_blob_chunk(&self, hash: String, index: u32) -> app::Result<Vec<u8>, AppError> {
+        self.blobs.chunk(&hash, index)
+    }
+
+    /// Blobs the document holds a reference to.
+    pub fn list_blobs(&self, document_id: Option<String>) -> app::Result<Vec<BlobInfo>, AppError> {
+        Ok(self.blobs.referenced_by(&self.document(document_id.as_deref())?.id))
+    }
+
+    /// Drop the document's reference to a blob. The blob is deleted with the
+    /// last reference. Requires the editor role on the document. Returns how
+    /// many documents still reference it.
+    pub fn release_blob(&mut self, hash: String, document_id: Option<String>) -> app::Result<u32, AppError> {
+        let caller = encode_executor_address()?;
+        let doc = self.document(document_id.as_deref())?;
+        doc.require_role(&caller, Role::Editor)?;
+        let id = doc.id.clone();
+        let remaining = self.blobs.release(&hash, &id)?;
+        env::emit(Event::BlobReleased { hash: hash.clone(), document_id: id.clone(), remaining });
+        self.audit("release_blob", Some(&id), 0, Some(hash));
+        Ok(remaining)
+    }
+
+    /// Apply a batch of edits. The whole batch is validated against the
+    /// current document first and either applied completely or rejected
+    /// with the offending op (see `ApplyMode`). When `options.batch_id` is
+    /// set, a retry of an already applied batch returns the version it
+    /// originally produced without applying it again.
+    pub fn apply_ops(
+        &mut self,
+        ops: Vec<DocOp>,
+        expected_version: Option<u64>,
+        options: Option<ApplyOptions>,
+        document_id: Option<String>,
+    ) -> app::Result<u64, AppError> {
+        let options = options.unwrap_or_default();
+        let editor_addr = encode_executor_address()?;
+        let idx = self.document_index(document_id.as_deref())?;
+        self.settle(idx);
+        let doc = &self.documents[idx];
+        if let Some(batch) = &options.batch_id {
+            match doc.check_batch(&editor_addr, batch) {
+                BatchCheck::Fresh => {}
+                BatchCheck::Replayed(version) => return Ok(version),
+                BatchCheck::OutOfOrder { high_water } => {
+                    return Err(GameError::BatchOutOfOrder { client_id: batch.client_id.clone(), seq: batch.seq, high_water });
                 }
+                BatchCheck::Expired => return Err(GameError::BatchExpired { client_id: batch.client_id.clone(), seq: batch.seq }),
             }
         }
+        if let Some(ev) = expected_version { if ev != doc.version { return Err(GameError::VersionConflict { expected: ev, actual: doc.version }); } }
+        self.quotas.check_ops(&ops)?;
+        let ops = crdt::validate_batch(ops, &doc.content, options.mode.unwrap_or_default(), options.unit.unwrap_or_default())?;
+        self.quotas.check_edit(doc.content.chars().count(), &ops)?;
+        doc.check_leases(&ops, &editor_addr, env::time_now())?;
+        let stamp = self.next_stamp();
+        let op_count = ops.len();
+
+        self.apply_validated(idx, ops, &editor_addr);
+        let doc = &mut self.documents[idx];
+        if let Some(batch) = &options.batch_id {
+            doc.record_batch(&editor_addr, batch, doc.version.wrapping_add(1), self.quotas.max_batch_clients);
+        }
+        let version = self.commit_edit(idx, editor_addr, stamp);
+        let id = self.documents[idx].id.clone();
+        self.audit("apply_ops", Some(&id), op_count, None);
+        Ok(version)
+    }
+
+    /// Matches of `pattern` in the document's current content, in order.
+    /// Each is reported as a visible range and as the item ids of its first
+    /// and last character, which stay valid while the text moves around.
+    pub fn find_in_document(&self, pattern: String, options: Option<FindOptions>, document_id: Option<String>) -> app::Result<Vec<FindMatch>, AppError> {
+        let options = options.unwrap_or_default();
+        let re = find::compile(&pattern, &options)?;
+        let doc = self.document(document_id.as_deref())?;
+        let found = doc.find_matches(&re, None, false);
+        Ok(doc.match_views(&found, options.unit.unwrap_or_default()))
+    }
+
+    /// Replace every match of `pattern` with `replacement` as one version,
+    /// which the caller can undo in one step. Regex patterns expand `$1` or
+    /// `${name}` in `replacement`. Returns the version unchanged when
+    /// nothing matches.
+    pub fn replace_all(
+        &mut self,
+        pattern: String,
+        replacement: String,
+        expected_version: Option<u64>,
+        options: Option<FindOptions>,
+        document_id: Option<String>,
+    ) -> app::Result<u64, AppError> {
+        let options = options.unwrap_or_default();
+        let re = find::compile(&pattern, &options)?;
+        let editor_addr = encode_executor_address()?;
+        let idx = self.document_index(document_id.as_deref())?;
+        self.settle(idx);
+        let doc = &self.documents[idx];
+        if let Some(ev) = expected_version { if ev != doc.version { return Err(GameError::VersionConflict { expected: ev, actual: doc.version }); } }
+        let found = doc.find_matches(&re, Some(&replacement), options.regex.unwrap_or(false));
+        if found.is_empty() {
+            return Ok(doc.version);
+        }
+        let ops = find::replacement_ops(found);
+        self.quotas.check_ops(&ops)?;
+        self.quotas.check_edit(doc.content.chars().count(), &ops)?;
+        doc.check_leases(&ops, &editor_addr, env::time_now())?;
+        let stamp = self.next_stamp();
+        let op_count = ops.len();
+
+        self.apply_validated(idx, ops, &editor_addr);
+        let version = self.commit_edit(idx, editor_addr, stamp);
+        let id = self.documents[idx].id.clone();
+        self.audit("replace_all", Some(&id), op_count, None);
+        Ok(version)
+    }
+
+    /// Undo the caller's most recent `apply_ops` batch that is not undone yet.
+    /// Only the caller's own changes are reverted, against the current
+    /// sequence; edits made by others since are kept.
+    pub fn undo(&mut self, document_id: Option<String>) -> app::Result<u64, AppError> {
+        self.step_history(document_id, false)
+    }
+
+    /// Reapply the caller's most recently undone batch. Any new `apply_ops`
+    /// batch from the caller clears what can be redone.
+    pub fn redo(&mut self, document_id: Option<String>) -> app::Result<u64, AppError> {
+        self.step_history(document_id, true)
+    }
+
+    /// Take an exclusive lease on `range` of the current content, or on the
+    /// whole document when `range` is `None`, for `ttl_ms` but at most ten
+    /// minutes. Others' edits touching it fail with `Leased` until it is
+    /// released or lapses. Acquiring the same range again renews the lease.
+    /// Requires the editor role.
+    pub fn acquire_lease(&mut self, range: Option<LeaseRange>, ttl_ms: u64, document_id: Option<String>) -> app::Result<LeaseView, AppError> {
+        let holder = encode_executor_address()?;
+        let now = env::time_now();
+        let idx = self.document_index(document_id.as_deref())?;
+        let doc = &mut self.documents[idx];
+        doc.require_role(&holder, Role::Editor)?;
+        let id = doc.take_lease(&holder, range, ttl_ms, now)?;
+        let Some(view) = doc.lease_views(now).into_iter().find(|l| l.id == id) else {
+            return Err(GameError::NotFound { kind: "lease", id: id.to_string() });
+        };
+        let document_id = doc.id.clone();
+        self.audit("acquire_lease", Some(&document_id), 0, Some(format!("lease {id}")));
+        Ok(view)
+    }
+
+    /// Give up a lease held by the caller. Owners of the document can also
+    /// break anyone's lease; everyone else gets `Leased`.
+    pub fn release_lease(&mut self, lease_id: u64, document_id: Option<String>) -> app::Result<(), AppError> {
+        let caller = encode_executor_address()?;
+        let idx = self.document_index(document_id.as_deref())?;
+        let lease = self.documents[idx].drop_lease(&caller, lease_id, env::time_now())?;
+        let id = self.documents[idx].id.clone();
+        let detail = if lease.holder == caller { format!("lease {lease_id}") } else { format!("lease {lease_id} of {}", lease.holder) };
+        self.audit("release_lease", Some(&id), 0, Some(detail));
+        Ok(())
+    }
+
+    /// Leases in force, with their ranges in the current content.
+    pub fn get_leases(&self, document_id: Option<String>) -> app::Result<Vec<LeaseView>, AppError> {
+        Ok(self.document(document_id.as_deref())?.lease_views(env::time_now()))
+    }
+
+    /// Documents containing every word of `query`, each word matching as a
+    /// prefix and case-insensitively, most matches first, at most `limit`.
+    pub fn search(&self, query: String, limit: usize) -> app::Result<Vec<SearchHit>, AppError> {
+        if query.chars().all(|c| !c.is_alphanumeric()) {
+            return Err(GameError::Empty { field: "query" });
+        }
+        Ok(self.run_search(&query, limit))
+    }
 
-        let mut visible: Vec<&Element> = self.elems.iter().filter(|e| e.visible).collect();
-        visible.sort_by(|a, b| a.uid.cmp(&b.uid));
+    /// Grant `address` `role` on the document, or revoke its role when
+    /// `role` is `None`. Only owners manage roles, and the last owner cannot
+    /// step down.
+    pub fn set_role(&mut self, address: String, role: Option<Role>, document_id: Option<String>) -> app::Result<(), AppError> {
+        let caller = encode_executor_address()?;
+        let idx = self.document_index(document_id.as_deref())?;
+        let doc = &mut self.documents[idx];
+        doc.require_role(&caller, Role::Owner)?;
+        if doc.role_of(&address) == role {
+            return Ok(());
+        }
+        doc.assign_role(&address, role)?;
+        let id = doc.id.clone();
+        let detail = match role {
+            Some(role) => format!("{address} {role:?}"),
+            None => format!("{address} revoked"),
+        };
+        self.audit("set_role", Some(&id), 0, Some(detail));
+        Ok(())
+    }
 
-        self.content = visible.iter().map(|e| char::from_u32(e.ch).unwrap_or('\u{FFFD}')).collect();
-        self.version = self.version.wrapping_add(1);
-        self.updated_ms = now;
-        self.last_editor = Some(editor_addr.clone());
+    pub fn get_roles(&self, document_id: Option<String>) -> app::Result<Vec<Member>, AppError> {
+        Ok(self.document(document_id.as_deref())?.members.clone())
+    }
 
-    app::emit!(Event::DocumentUpdated { content: self.content.clone(), version: self.version, editor: editor_addr.clone() });
-        Ok(self.version)
+    /// Lifecycle status, counting scheduled publishes and expiries that
+    /// are due even if nothing has written the document since.
+    pub fn get_status(&self, document_id: Option<String>) -> app::Result<DocStatus, AppError> {
+        Ok(self.document(document_id.as_deref())?.status_at(env::time_now()))
     }
 
-    pub fn get_document(&self) -> app::Result<DocumentView> {
-        Ok(DocumentView { content: self.content.clone(), version: self.version, updated_ms: self.updated_ms, last_editor: self.last_editor.clone() })
+    /// Move the document through its lifecycle: editors submit drafts for
+    /// review or withdraw them, owners archive and restore. Publishing goes
+    /// through `publish`.
+    pub fn set_status(&mut self, status: DocStatus, document_id: Option<String>) -> app::Result<(), AppError> {
+        let caller = encode_executor_address()?;
+        let idx = self.document_index(document_id.as_deref())?;
+        if status == DocStatus::Published {
+            return Err(GameError::InvalidTransition { from: self.documents[idx].status_at(env::time_now()), to: status });
+        }
+        self.settle(idx);
+        let doc = &mut self.documents[idx];
+        let from = doc.transition(&caller, status)?;
+        let id = doc.id.clone();
+        env::emit(Event::StatusChanged { document_id: id.clone(), from, to: status, by: caller });
+        self.audit("set_status", Some(&id), 0, Some(format!("{from:?} -> {status:?}")));
+        Ok(())
+    }
+
+    /// Publish a document in review, or republish a published one, as a
+    /// reviewer. Freezes the current text and version as what
+    /// `get_published` returns until the next publish, replacing any
+    /// scheduled publish.
+    pub fn publish(&mut self, document_id: Option<String>) -> app::Result<PublishedView, AppError> {
+        let caller = encode_executor_address()?;
+        let now = env::time_now();
+        let idx = self.document_index(document_id.as_deref())?;
+        self.settle(idx);
+        let doc = &mut self.documents[idx];
+        let from = doc.transition(&caller, DocStatus::Published)?;
+        doc.take_snapshot(&caller, now);
+        if from != DocStatus::Published {
+            env::emit(Event::StatusChanged { document_id: doc.id.clone(), from, to: DocStatus::Published, by: caller.clone() });
+        }
+        env::emit(Event::DocumentPublished { document_id: doc.id.clone(), version: doc.version, published_by: caller });
+        let Some(view) = doc.published_view(now) else {
+            return Err(GameError::NotFound { kind: "published document", id: doc.id.clone() });
+        };
+        let id = doc.id.clone();
+        self.audit("publish", Some(&id), 0, None);
+        Ok(view)
+    }
+
+    /// Freeze the current text now and publish it at `publish_at_ms`, until
+    /// `expires_at_ms` if given. Readers keep getting the previous snapshot
+    /// until then. Needs the same role and status as `publish`; a time that
+    /// has already passed publishes right away.
+    pub fn schedule_publish(&mut self, publish_at_ms: u64, expires_at_ms: Option<u64>, document_id: Option<String>) -> app::Result<(), AppError> {
+        let caller = encode_executor_address()?;
+        let idx = self.document_index(document_id.as_deref())?;
+        self.settle(idx);
+        self.documents[idx].schedule_snapshot(&caller, publish_at_ms, expires_at_ms)?;
+        self.settle(idx);
+        let id = self.documents[idx].id.clone();
+        self.audit("schedule_publish", Some(&id), 0, None);
+        Ok(())
+    }
+
+    /// Set or clear when the scheduled snapshot, or the published one when
+    /// none is scheduled, is taken down. Taking it down archives a
+    /// published document.
+    pub fn set_expiry(&mut self, expires_at_ms: Option<u64>, document_id: Option<String>) -> app::Result<(), AppError> {
+        let caller = encode_executor_address()?;
+        let idx = self.document_index(document_id.as_deref())?;
+        self.settle(idx);
+        self.documents[idx].set_expiry(&caller, expires_at_ms)?;
+        self.settle(idx);
+        let id = self.documents[idx].id.clone();
+        self.audit("set_expiry", Some(&id), 0, None);
+        Ok(())
+    }
+
+    /// Apply every scheduled publish and expiry that is due, emitting their
+    /// events. Returns how many took effect. Writes to a document do the
+    /// same for it, so this only matters when nothing else is happening.
+    pub fn run_schedules(&mut self) -> app::Result<u32, AppError> {
+        let applied = self.settle_all();
+        if applied > 0 {
+            self.audit("run_schedules", None, 0, None);
+        }
+        Ok(applied)
+    }
+
+    /// The published snapshot readers see now, for readers. Edits made
+    /// since do not show until the document is published again, and a
+    /// scheduled snapshot shows from its publish time on.
+    pub fn get_published(&self, document_id: Option<String>) -> app::Result<PublishedView, AppError> {
+        let doc = self.document(document_id.as_deref())?;
+        match doc.published_view(env::time_now()) {
+            Some(view) => Ok(view),
+            None => Err(GameError::NotFound { kind: "published document", id: doc.id.clone() }),
+        }
+    }
+
+    /// The visible text split into runs of characters inserted by the same
+    /// author at the same time and version, in document order.
+    pub fn get_blame(&self, document_id: Option<String>) -> app::Result<Vec<BlameRun>, AppError> {
+        Ok(self.document(document_id.as_deref())?.blame_runs())
+    }
+
+    /// Characters inserted, deleted and still visible per author, most
+    /// surviving first.
+    pub fn get_contributions(&self, document_id: Option<String>) -> app::Result<Vec<AuthorStats>, AppError> {
+        Ok(self.document(document_id.as_deref())?.author_stats())
+    }
+
+    /// The caller's notifications, newest first, or only those not read yet.
+    pub fn list_notifications(&self, unread_only: bool) -> app::Result<Vec<Notification>, AppError> {
+        let caller = encode_executor_address()?;
+        Ok(self
+            .notifications
+            .iter()
+            .rev()
+            .filter(|n| n.recipient == caller && !(unread_only && n.read))
+            .cloned()
+            .collect())
+    }
+
+    /// Mark the caller's notifications `notification_ids`, or all of them
+    /// when `None`, as read. Returns how many were unread.
+    pub fn mark_read(&mut self, notification_ids: Option<Vec<u64>>) -> app::Result<u32, AppError> {
+        let caller = encode_executor_address()?;
+        let marked = self.mark_notifications(&caller, notification_ids.as_deref())?;
+        if marked > 0 {
+            self.audit("mark_read", None, 0, None);
+        }
+        Ok(marked)
+    }
+
+    /// Logged calls matching `filter`, newest first, at most `limit` (capped
+    /// at 200) per page. Pass the returned `next_before_seq` as
+    /// `before_seq` for the next page.
+    pub fn get_audit_log(&self, filter: Option<AuditFilter>, before_seq: Option<u64>, limit: usize) -> app::Result<AuditPage, AppError> {
+        Ok(self.audit_page(&filter.unwrap_or_default(), before_seq, limit))
+    }
+
+    /// Size limits in force for the context.
+    pub fn get_quotas(&self) -> app::Result<Quotas, AppError> {
+        Ok(self.quotas.clone())
+    }
+
+    /// Replace the size limits of the context. Only the context owner may,
+    /// or owners of the default document in contexts created before owners
+    /// were recorded; every limit must be positive.
+    pub fn set_quotas(&mut self, quotas: Quotas) -> app::Result<(), AppError> {
+        let editor_addr = encode_executor_address()?;
+        match &self.owner {
+            Some(owner) if *owner != editor_addr => return Err(GameError::NotContextOwner { owner: owner.clone() }),
+            Some(_) => {}
+            None => self.document(None)?.require_role(&editor_addr, Role::Owner)?,
+        }
+        quotas.validate()?;
+        if quotas == self.quotas {
+            return Ok(());
+        }
+        let detail = format!("{quotas:?}");
+        self.quotas = quotas;
+        self.audit("set_quotas", None, 0, Some(detail));
+        Ok(())
+    }
+
+    /// Rewrite state loaded from an older schema in the current layout.
+    /// Returns the schema now in effect; a no-op for up-to-date contexts.
+    pub fn migrate(&mut self) -> app::Result<u16, AppError> {
+        if self.loaded_schema < migration::CURRENT_SCHEMA {
+            env::emit(Event::StateMigrated { from_schema: self.loaded_schema, to_schema: migration::CURRENT_SCHEMA });
+            self.loaded_schema = migration::CURRENT_SCHEMA;
+            self.audit("migrate", None, 0, None);
+        }
+        Ok(self.loaded_schema)
+    }
+
+    pub fn get_document(&self, document_id: Option<String>) -> app::Result<DocumentView, AppError> {
+        Ok(self.document(document_id.as_deref())?.view())
     }
 
     // Presence methods embedded in SharedDocument
     /// Ping from the current executor. Records the executor address, payload and timestamp.
-    pub fn ping(&mut self, addr: String, payload: String) -> app::Result<()> {
+    /// `addr` must be the executor's own address, so every executor holds at most one entry.
+    pub fn ping(&mut self, addr: String, payload: String) -> app::Result<(), AppError> {
+        if addr != encode_executor_address()? {
+            return Err(GameError::NotCaller { address: addr });
+        }
+        let known = self.presence_entries.iter().any(|e| e.address == addr);
+        self.quotas.check_ping(&payload, known, self.presence_entries.len())?;
         let now = env::time_now();
-
-        for e in self.presence_entries.iter_mut() {
-            if e.address == addr {
-                e.last_seen_ms = now;
-                e.payload = payload.clone();
-                return Ok(());
-            }
+        if self.refresh_presence(&addr, &payload, now) {
+            return Ok(());
         }
         self.presence_entries.push(PresenceEntry { address: addr.clone(), last_seen_ms: now, payload });
-        
-        app::emit!(Event::UserPing { addr: addr.clone(), last_seen_ms: now });
+        let stamp = self.next_stamp();
+        env::emit(Event::UserPing { addr: addr.clone(), last_seen_ms: now, stamp });
         Ok(())
     }
 
     /// Return active entries within the provided TTL (milliseconds). If ttl_ms is None, returns all entries.
-    pub fn get_active_users(&self, ttl_ms: Option<u64>) -> app::Result<Vec<PresenceEntry>> {
-        let now = env::time_now();
-        let mut out: Vec<PresenceEntry> = Vec::new();
-        for e in self.presence_entries.iter() {
-            if let Some(ttl) = ttl_ms {
-                if e.last_seen_ms + ttl >= now {
-                    out.push(e.clone());
-                }
-            } else {
-                out.push(e.clone());
-            }
-        }
-        Ok(out)
+    pub fn get_active_users(&self, ttl_ms: Option<u64>) -> app::Result<Vec<PresenceEntry>, AppError> {
+        Ok(self.active_presence(ttl_ms, env::time_now()))
     }
 
     /// Cleaning helper to remove stale entries older than ttl_ms
-    pub fn purge_stale(&mut self, ttl_ms: u64) -> app::Result<()> {
+    pub fn purge_stale(&mut self, ttl_ms: u64) -> app::Result<(), AppError> {
         let now = env::time_now();
+        let presence = self.presence_entries.len();
         self.presence_entries.retain(|e| e.last_seen_ms + ttl_ms >= now);
+        let mut purged = presence - self.presence_entries.len();
+        for doc in self.documents.iter_mut() {
+            let leases = doc.leases.leases.len();
+            doc.expire_leases(now);
+            purged += leases - doc.leases.leases.len();
+        }
+        if self.settle_all() > 0 || purged > 0 {
+            self.audit("purge_stale", None, 0, Some(format!("ttl {ttl_ms} ms")));
+        }
         Ok(())
     }
 }
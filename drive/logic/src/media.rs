@@ -0,0 +1,114 @@
+//! Registry of images and embeds used by the documents of the drive.
+//!
+//! Uploads stay with the media host; the registry records what the editor
+//! needs to render and describe them. Documents refer to an asset through a
+//! `data-media-id="<asset id>"` attribute in their content, so references
+//! follow every edit, undo and template copy without separate bookkeeping.
+//! Content that holds serialised JSON, as the drive's posts do, escapes the
+//! quotes to `data-media-id=\"<asset id>\"`, which is recognised as well.
+//! Alt text and captions are shared by every document showing the asset
+//! and versioned, so concurrent edits are caught with `expected_version`
+//! like document edits.
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+
+use crate::{AppError, SharedDocument};
+
+/// Attribute through which document content refers to an asset.
+const MEDIA_ATTRIBUTE: &str = "data-media-id=";
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct MediaAsset {
+    pub asset_id: String,
+    pub url: String,
+    pub mime: String,
+    /// Natural size in pixels, when the uploader knows it
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub alt: String,
+    pub caption: String,
+    pub uploader: String,
+    /// Digest of the uploaded bytes as reported by the uploader
+    pub checksum: String,
+    pub created_ms: u64,
+    /// Bumped by every alt text or caption edit
+    pub version: u64,
+    pub last_editor: String,
+}
+
+/// What a client knows about an upload when registering it.
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct NewMedia {
+    pub url: String,
+    pub mime: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub alt: Option<String>,
+    pub caption: Option<String>,
+    pub checksum: String,
+}
+
+impl NewMedia {
+    pub(crate) fn validate(&self) -> Result<(), AppError> {
+        if self.url.is_empty() {
+            return Err(AppError::Empty { field: "url" });
+        }
+        if !valid_mime(&self.mime) {
+            return Err(AppError::InvalidMime { mime: self.mime.clone() });
+        }
+        if self.checksum.is_empty() {
+            return Err(AppError::Empty { field: "checksum" });
+        }
+        Ok(())
+    }
+}
+
+/// Whether `mime` has the `type/subtype` shape.
+pub(crate) fn valid_mime(mime: &str) -> bool {
+    matches!(mime.split_once('/'), Some((kind, sub)) if !kind.is_empty() && !sub.is_empty())
+}
+
+/// Asset ids referenced by `content`, in order of first use.
+pub(crate) fn references(content: &str) -> Vec<&str> {
+    let mut ids: Vec<&str> = Vec::new();
+    let mut rest = content;
+    while let Some(at) = rest.find(MEDIA_ATTRIBUTE) {
+        rest = &rest[at + MEDIA_ATTRIBUTE.len()..];
+        let Some(value) = rest.strip_prefix('"').or_else(|| rest.strip_prefix("\\\"")) else {
+            continue;
+        };
+        let Some(end) = value.find(['"', '\\']) else {
+            break;
+        };
+        let id = &value[..end];
+        if !id.is_empty() && !ids.contains(&id) {
+            ids.push(id);
+        }
+        rest = &value[end..];
+    }
+    ids
+}
+
+impl SharedDocument {
+    pub(crate) fn media_index(&self, asset_id: &str) -> Result<usize, AppError> {
+        self.media.iter().position(|m| m.asset_id == asset_id).ok_or_else(|| AppError::NotFound { kind: "media", id: asset_id.to_string() })
+    }
+
+    /// First unused `media-<n>`.
+    pub(crate) fn new_media_id(&self) -> String {
+        (1..)
+            .map(|n| format!("media-{n}"))
+            .find(|id| self.media_index(id).is_err())
+            .expect("unbounded ids")
+    }
+
+    /// Whether any document's content refers to `asset_id`.
+    pub(crate) fn media_referenced(&self, asset_id: &str) -> bool {
+        self.documents.iter().any(|d| references(&d.content).contains(&asset_id))
+    }
+}
@@ -0,0 +1,124 @@
+//! `@mentions` of collaborators and the notifications they leave.
+//!
+//! A mention is `@` followed by an executor address, at the start of the
+//! text or after a character that cannot be part of a word, so e-mail
+//! addresses do not count. Only known authors are notified: anyone holding
+//! a role on a document or having written text in one. Edits are compared
+//! with the text they replace, so a mention typed one keystroke at a time
+//! notifies once, when it is complete, and text that merely moves does not
+//! notify again.
+
+use std::collections::BTreeMap;
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+
+use crate::search::{snippet, SearchMatch};
+use crate::{AppError, SharedDocument};
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct Notification {
+    pub id: u64,
+    pub recipient: String,
+    pub document_id: String,
+    pub mentioned_by: String,
+    /// Document version whose text added the mention
+    pub version: u64,
+    /// Text around the mention, with `…` where it was cut
+    pub excerpt: String,
+    pub created_ms: u64,
+    pub read: bool,
+}
+
+/// Mentioned names in `text`, with the scalar position of their first
+/// mention and how often they occur.
+fn mentions(text: &str) -> BTreeMap<&str, (usize, usize)> {
+    let mut found: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    let mut prev: Option<char> = None;
+    for (index, (at, ch)) in text.char_indices().enumerate() {
+        if ch == '@' && !prev.is_some_and(|p| p.is_alphanumeric()) {
+            let rest = &text[at + 1..];
+            let end = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+            if end > 0 {
+                found.entry(&rest[..end]).or_insert((index, 0)).1 += 1;
+            }
+        }
+        prev = Some(ch);
+    }
+    found
+}
+
+/// Names mentioned more often in `new` than in `old`, with the position of
+/// their first mention in `new`.
+pub(crate) fn added_mentions<'a>(old: &str, new: &'a str) -> Vec<(&'a str, usize)> {
+    let before = mentions(old);
+    mentions(new)
+        .into_iter()
+        .filter(|(name, (_, count))| before.get(name).map_or(0, |(_, c)| *c) < *count)
+        .map(|(name, (index, _))| (name, index))
+        .collect()
+}
+
+impl SharedDocument {
+    /// Whether `address` holds a role on a document or wrote text in one.
+    pub(crate) fn is_known_author(&self, address: &str) -> bool {
+        self.documents
+            .iter()
+            .any(|d| d.members.iter().any(|m| m.address == address) || d.elems.iter().any(|e| e.editor == address))
+    }
+
+    /// Leave a notification for every known author whose mentions in the
+    /// document at `idx` grew compared with `old`, dropping each recipient's
+    /// oldest past `Quotas::max_notifications`. Returns the new entries.
+    pub(crate) fn record_mentions(&mut self, idx: usize, old: &str, by: &str, now: u64) -> Vec<Notification> {
+        let doc = &self.documents[idx];
+        let mut added = Vec::new();
+        for (name, index) in added_mentions(old, &doc.content) {
+            if name == by || !self.is_known_author(name) {
+                continue;
+            }
+            let len = name.chars().count() + 1;
+            added.push(Notification {
+                id: self.notifications.last().map_or(1, |n| n.id + 1) + added.len() as u64,
+                recipient: name.to_string(),
+                document_id: doc.id.clone(),
+                mentioned_by: by.to_string(),
+                version: doc.version,
+                excerpt: snippet(&doc.content, SearchMatch { index, len }).0,
+                created_ms: now,
+                read: false,
+            });
+        }
+        self.notifications.extend(added.iter().cloned());
+        for n in &added {
+            let held = self.notifications.iter().filter(|m| m.recipient == n.recipient).count() as u64;
+            let mut excess = held.saturating_sub(self.quotas.max_notifications);
+            self.notifications.retain(|m| {
+                let drop = excess > 0 && m.recipient == n.recipient;
+                excess -= drop as u64;
+                !drop
+            });
+        }
+        added
+    }
+
+    /// Mark the caller's notifications `ids` as read. Returns how many were
+    /// unread.
+    pub(crate) fn mark_notifications(&mut self, recipient: &str, ids: Option<&[u64]>) -> Result<u32, AppError> {
+        if let Some(ids) = ids {
+            if let Some(id) = ids.iter().find(|id| !self.notifications.iter().any(|n| n.id == **id && n.recipient == recipient)) {
+                return Err(AppError::NotFound { kind: "notification", id: id.to_string() });
+            }
+        }
+        let mut marked = 0;
+        for n in self.notifications.iter_mut().filter(|n| n.recipient == recipient && !n.read) {
+            if ids.is_none_or(|ids| ids.contains(&n.id)) {
+                n.read = true;
+                marked += 1;
+            }
+        }
+        Ok(marked)
+    }
+}
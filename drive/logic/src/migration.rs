@@ -0,0 +1,359 @@
+//! State schema versioning and migrations.
+//!
+//! `SharedDocument` is persisted as a small envelope: the `STATE_MAGIC` bytes,
+//! the schema version as a `u16`, then the fields of that schema. Contexts
+//! created before the envelope existed store the bare Borsh struct; those
+//! historical layouts are frozen below and recognised by decoding the whole
+//! blob against each of them, newest first.
+//!
+//! Adding a field to the state means bumping `CURRENT_SCHEMA`, writing the
+//! field in `write_state` and reading it in `read_fields` behind a schema
+//! check with a sensible default for older blobs. Structural changes get an
+//! explicit upgrade step instead.
+
+use std::io::{self, Read, Write};
+
+use calimero_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+
+use crate::audit::AuditEntry;
+use crate::blobs::BlobStore;
+use crate::crdt::{Element, HlcTimestamp};
+use crate::document::{Document, DEFAULT_DOCUMENT};
+use crate::media::MediaAsset;
+use crate::mentions::Notification;
+use crate::presence::PresenceEntry;
+use crate::quotas::Quotas;
+use crate::search::SearchIndex;
+use crate::templates::Template;
+use crate::SharedDocument;
+
+/// Prefix of every enveloped state blob.
+pub(crate) const STATE_MAGIC: [u8; 4] = *b"SDOC";
+
+/// Schema written by this build.
+///
+/// - 0: `document.rs` layout (no presence, `id_nonce` uid counter)
+/// - 1: `lib.rs` layout with `presence_entries`
+/// - 2: `id_nonce` replaced by the hybrid logical clock
+/// - 3: first enveloped layout: several documents per context with RGA
+///   sequences, plus the search index and the context-wide registries
+pub(crate) const CURRENT_SCHEMA: u16 = 3;
+
+/// `Element` before it recorded its origin (schemas 0 to 2).
+#[derive(BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+struct LegacyElement {
+    uid: u128,
+    ch: u32,
+    visible: bool,
+    created_ms: u64,
+    editor: String,
+}
+
+/// Older layouts rendered their content from the visible elements sorted by
+/// uid, whatever their place in the vector, so anchoring every element on
+/// its predecessor in uid order reproduces the text they stored.
+fn chain_origins(mut elems: Vec<LegacyElement>) -> Vec<Element> {
+    elems.sort_by_key(|e| e.uid);
+    let mut origin = None;
+    elems
+        .into_iter()
+        .map(|e| {
+            let elem = Element { uid: e.uid, origin, item: e.uid, ch: e.ch, visible: e.visible, created_ms: e.created_ms, editor: e.editor, version: 0 };
+            origin = Some(e.uid);
+            elem
+        })
+        .collect()
+}
+
+#[derive(BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+struct LegacyV0 {
+    content: String,
+    version: u64,
+    updated_ms: u64,
+    last_editor: Option<String>,
+    elems: Vec<LegacyElement>,
+    #[allow(dead_code)]
+    id_nonce: u64,
+}
+
+#[derive(BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+struct LegacyV1 {
+    content: String,
+    version: u64,
+    updated_ms: u64,
+    last_editor: Option<String>,
+    elems: Vec<LegacyElement>,
+    presence_entries: Vec<PresenceEntry>,
+    #[allow(dead_code)]
+    id_nonce: u64,
+}
+
+#[derive(BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+struct LegacyV2 {
+    content: String,
+    version: u64,
+    updated_ms: u64,
+    last_editor: Option<String>,
+    elems: Vec<LegacyElement>,
+    presence_entries: Vec<PresenceEntry>,
+    clock: HlcTimestamp,
+}
+
+impl From<LegacyV0> for LegacyV1 {
+    fn from(old: LegacyV0) -> Self {
+        LegacyV1 {
+            content: old.content,
+            version: old.version,
+            updated_ms: old.updated_ms,
+            last_editor: old.last_editor,
+            elems: old.elems,
+            presence_entries: Vec::new(),
+            id_nonce: old.id_nonce,
+        }
+    }
+}
+
+impl From<LegacyV1> for LegacyV2 {
+    fn from(old: LegacyV1) -> Self {
+        // Old uids keep the wall clock in their high 64 bits, so they still
+        // sort correctly; the clock only has to start past the last update.
+        LegacyV2 {
+            content: old.content,
+            version: old.version,
+            updated_ms: old.updated_ms,
+            last_editor: old.last_editor,
+            elems: old.elems,
+            presence_entries: old.presence_entries,
+            clock: HlcTimestamp { physical_ms: old.updated_ms, logical: 0 },
+        }
+    }
+}
+
+impl LegacyV2 {
+    fn upgrade(self, from: u16) -> SharedDocument {
+        let mut document = Document::new(DEFAULT_DOCUMENT, self.updated_ms);
+        document.content = self.content;
+        document.version = self.version;
+        document.last_editor = self.last_editor;
+        document.elems = chain_origins(self.elems);
+        single_document(document, self.presence_entries, self.clock, from)
+    }
+}
+
+/// State of the schemas that held one document, which becomes the default
+/// document of the drive and the only one in its search index.
+fn single_document(mut document: Document, presence_entries: Vec<PresenceEntry>, clock: HlcTimestamp, from: u16) -> SharedDocument {
+    claim_for_last_editor(&mut document);
+    let mut search_index = SearchIndex::default();
+    search_index.update(&document.id, "", &document.content);
+    SharedDocument {
+        documents: vec![document],
+        presence_entries,
+        clock,
+        search_index,
+        templates: Vec::new(),
+        media: Vec::new(),
+        blobs: BlobStore::default(),
+        notifications: Vec::new(),
+        audit_log: Vec::new(),
+        quotas: Quotas::default(),
+        owner: None,
+        loaded_schema: from,
+    }
+}
+
+/// Write the state in the current enveloped layout.
+pub(crate) fn write_state<W: Write>(state: &SharedDocument, writer: &mut W) -> io::Result<()> {
+    STATE_MAGIC.serialize(writer)?;
+    CURRENT_SCHEMA.serialize(writer)?;
+    state.documents.serialize(writer)?;
+    state.presence_entries.serialize(writer)?;
+    state.clock.serialize(writer)?;
+    state.search_index.serialize(writer)?;
+    state.templates.serialize(writer)?;
+    state.media.serialize(writer)?;
+    state.blobs.serialize(writer)?;
+    state.notifications.serialize(writer)?;
+    state.audit_log.serialize(writer)?;
+    state.quotas.serialize(writer)?;
+    state.owner.serialize(writer)
+}
+
+/// Read a state blob of any known schema, upgrading it to the current
+/// layout, and catch the clock up with the elements it holds.
+pub(crate) fn read_state<R: Read>(reader: &mut R) -> io::Result<SharedDocument> {
+    let mut state = decode_state(reader)?;
+    state.observe_elements();
+    Ok(state)
+}
+
+fn decode_state<R: Read>(reader: &mut R) -> io::Result<SharedDocument> {
+    let mut head = [0u8; 4];
+    let filled = read_up_to(reader, &mut head)?;
+    if filled == head.len() && head == STATE_MAGIC {
+        let schema = u16::deserialize_reader(reader)?;
+        return read_fields(reader, schema);
+    }
+
+    let mut bytes = head[..filled].to_vec();
+    let _ = reader.read_to_end(&mut bytes)?;
+    upgrade_legacy(&bytes)
+}
+
+fn read_fields<R: Read>(reader: &mut R, schema: u16) -> io::Result<SharedDocument> {
+    if schema > CURRENT_SCHEMA {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("state schema {schema} is newer than supported schema {CURRENT_SCHEMA}"),
+        ));
+    }
+    if schema < 3 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("schema {schema} is never enveloped")));
+    }
+    Ok(SharedDocument {
+        documents: Vec::<Document>::deserialize_reader(reader)?,
+        presence_entries: Vec::<PresenceEntry>::deserialize_reader(reader)?,
+        clock: HlcTimestamp::deserialize_reader(reader)?,
+        search_index: SearchIndex::deserialize_reader(reader)?,
+        templates: Vec::<Template>::deserialize_reader(reader)?,
+        media: Vec::<MediaAsset>::deserialize_reader(reader)?,
+        blobs: BlobStore::deserialize_reader(reader)?,
+        notifications: Vec::<Notification>::deserialize_reader(reader)?,
+        audit_log: Vec::<AuditEntry>::deserialize_reader(reader)?,
+        quotas: Quotas::deserialize_reader(reader)?,
+        owner: Option::<String>::deserialize_reader(reader)?,
+        loaded_schema: schema,
+    })
+}
+
+/// Documents before roles existed are owned by whoever edited them last.
+fn claim_for_last_editor(document: &mut Document) {
+    if let Some(editor) = document.last_editor.clone() {
+        document.claim(&editor);
+    }
+}
+
+fn upgrade_legacy(bytes: &[u8]) -> io::Result<SharedDocument> {
+    if let Ok(v2) = borsh::from_slice::<LegacyV2>(bytes) {
+        return Ok(v2.upgrade(2));
+    }
+    if let Ok(v1) = borsh::from_slice::<LegacyV1>(bytes) {
+        return Ok(LegacyV2::from(v1).upgrade(1));
+    }
+    if let Ok(v0) = borsh::from_slice::<LegacyV0>(bytes) {
+        return Ok(LegacyV2::from(LegacyV1::from(v0)).upgrade(0));
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "state matches no known schema"))
+}
+
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acl::Role;
+    use crate::crdt::uid_timestamp;
+    use crate::publishing::DocStatus;
+
+    const EDITOR: &str = "4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi";
+
+    fn migrate(bytes: &[u8]) -> io::Result<SharedDocument> {
+        read_state(&mut &bytes[..])
+    }
+
+    fn assert_fixture(state: &SharedDocument, schema: u16) {
+        assert_eq!(state.loaded_schema, schema);
+        assert_eq!(state.documents.len(), 1);
+        let doc = &state.documents[0];
+        assert_eq!(doc.id, DEFAULT_DOCUMENT);
+        assert_eq!(doc.content, "helo");
+        assert_eq!(doc.version, 3);
+        assert_eq!(doc.updated_ms, 1_700_000_000_000);
+        assert_eq!(doc.last_editor.as_deref(), Some(EDITOR));
+        assert_eq!(doc.elems.len(), 5);
+        assert_eq!(doc.elems.iter().filter(|e| e.visible).count(), 4);
+        assert!(doc.elems.iter().all(|e| uid_timestamp(e.uid) <= state.clock));
+        assert_eq!(state.run_search("hel", 10).len(), 1);
+    }
+
+    #[test]
+    fn loads_v0_document_layout() {
+        let state = migrate(include_bytes!("../tests/fixtures/state_v0.bin")).unwrap();
+        assert_fixture(&state, 0);
+        assert!(state.presence_entries.is_empty());
+        assert_eq!(state.clock.physical_ms, 1_700_000_000_000);
+    }
+
+    #[test]
+    fn loads_v1_presence_layout() {
+        let state = migrate(include_bytes!("../tests/fixtures/state_v1.bin")).unwrap();
+        assert_fixture(&state, 1);
+        assert_eq!(state.presence_entries.len(), 1);
+        assert_eq!(state.presence_entries[0].address, EDITOR);
+        assert_eq!(state.clock.physical_ms, 1_700_000_000_000);
+    }
+
+    #[test]
+    fn loads_v2_clock_layout() {
+        let state = migrate(include_bytes!("../tests/fixtures/state_v2.bin")).unwrap();
+        assert_fixture(&state, 2);
+        assert_eq!(state.presence_entries.len(), 1);
+        assert_eq!(state.clock, HlcTimestamp { physical_ms: 1_700_000_000_000, logical: 7 });
+    }
+
+    #[test]
+    fn legacy_elements_follow_the_rendered_content() {
+        let state = migrate(include_bytes!("../tests/fixtures/state_v1_insert.bin")).unwrap();
+        let doc = &state.documents[0];
+        assert_eq!(doc.content, "hlloe");
+        let rendered: String = doc.elems.iter().filter(|e| e.visible).filter_map(|e| char::from_u32(e.ch)).collect();
+        assert_eq!(rendered, doc.content);
+        assert_eq!(doc.elems[0].origin, None);
+        assert!(doc.elems.windows(2).all(|w| w[1].origin == Some(w[0].uid)));
+    }
+
+    #[test]
+    fn legacy_documents_belong_to_their_last_editor() {
+        let state = migrate(include_bytes!("../tests/fixtures/state_v2.bin")).unwrap();
+        let doc = &state.documents[0];
+        assert!(doc.elems.iter().all(|e| e.version == 0 && e.item == e.uid));
+        assert!(doc.deletions.is_empty());
+        assert_eq!(doc.role_of(EDITOR), Some(Role::Owner));
+        assert_eq!(doc.status, DocStatus::Draft);
+        assert_eq!(state.owner, None);
+    }
+
+    #[test]
+    fn upgraded_state_round_trips_in_current_envelope() {
+        let state = migrate(include_bytes!("../tests/fixtures/state_v1.bin")).unwrap();
+        let bytes = borsh::to_vec(&state).unwrap();
+        assert_eq!(bytes[..4], STATE_MAGIC);
+        assert_eq!(u16::from_le_bytes([bytes[4], bytes[5]]), CURRENT_SCHEMA);
+
+        let reloaded = migrate(&bytes).unwrap();
+        assert_fixture(&reloaded, CURRENT_SCHEMA);
+        assert_eq!(reloaded.clock, state.clock);
+    }
+
+    #[test]
+    fn rejects_unknown_layouts() {
+        assert!(migrate(b"not a state blob").is_err());
+
+        let mut future = STATE_MAGIC.to_vec();
+        future.extend_from_slice(&(CURRENT_SCHEMA + 1).to_le_bytes());
+        assert!(migrate(&future).is_err());
+    }
+}
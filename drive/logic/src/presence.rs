@@ -0,0 +1,46 @@
+//! Presence tracking for active collaborators.
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+
+use crate::SharedDocument;
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct PresenceEntry {
+    pub address: String,
+    pub last_seen_ms: u64,
+    /// Optional payload provided by the client (can contain a short status or nonce)
+    pub payload: String,
+}
+
+impl SharedDocument {
+    /// Refresh an existing entry for `addr`. Returns `false` when the address
+    /// has not pinged before.
+    pub(crate) fn refresh_presence(&mut self, addr: &str, payload: &str, now: u64) -> bool {
+        for e in self.presence_entries.iter_mut() {
+            if e.address == addr {
+                e.last_seen_ms = now;
+                e.payload = payload.to_string();
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Entries seen within `ttl_ms` of `now`, or all entries without a TTL.
+    pub(crate) fn active_presence(&self, ttl_ms: Option<u64>, now: u64) -> Vec<PresenceEntry> {
+        let mut out: Vec<PresenceEntry> = Vec::new();
+        for e in self.presence_entries.iter() {
+            if let Some(ttl) = ttl_ms {
+                if e.last_seen_ms + ttl >= now {
+                    out.push(e.clone());
+                }
+            } else {
+                out.push(e.clone());
+            }
+        }
+        out
+    }
+}
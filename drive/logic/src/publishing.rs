@@ -0,0 +1,194 @@
+//! Document lifecycle and published snapshots.
+//!
+//! A document moves from draft to review to published and eventually to
+//! archived, each step gated on a role. Publishing freezes the current text
+//! and version into a snapshot; readers get that snapshot while editors keep
+//! working on the live text, until the next publish replaces it.
+//!
+//! A publish can also be scheduled: the reviewed text is frozen right away
+//! but only shown from the publish time on, and a publication can expire,
+//! which archives the document. Readers' views follow `env::time_now()`
+//! directly; the stored status catches up, with events, on the next call
+//! that writes the document or on `run_schedules`.
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+
+use crate::acl::Role;
+use crate::document::Document;
+use crate::AppError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub enum DocStatus {
+    #[default]
+    Draft,
+    InReview,
+    Published,
+    Archived,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct PublishedView {
+    pub document_id: String,
+    pub content: String,
+    /// Document version the snapshot was taken at
+    pub version: u64,
+    pub published_ms: u64,
+    pub published_by: String,
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+pub(crate) struct Snapshot {
+    pub(crate) content: String,
+    pub(crate) version: u64,
+    pub(crate) published_ms: u64,
+    pub(crate) published_by: String,
+}
+
+/// Role needed to move a document from `from` to `to`, if the move is allowed.
+fn transition_role(from: DocStatus, to: DocStatus) -> Option<Role> {
+    use DocStatus::*;
+    match (from, to) {
+        (Draft, InReview) | (InReview, Draft) | (Published, InReview) => Some(Role::Editor),
+        (InReview, Published) | (Published, Published) => Some(Role::Reviewer),
+        (Draft | InReview | Published, Archived) | (Archived, Draft) => Some(Role::Owner),
+        _ => None,
+    }
+}
+
+/// Lifecycle change a due schedule made, to be announced.
+pub(crate) enum Scheduled {
+    Published { from: DocStatus, snapshot: Snapshot },
+    Expired { version: u64, by: String, archived: bool },
+}
+
+impl Document {
+    /// Check that `caller` may move the document to `to`.
+    pub(crate) fn check_transition(&self, caller: &str, to: DocStatus) -> Result<(), AppError> {
+        let Some(role) = transition_role(self.status, to) else {
+            return Err(AppError::InvalidTransition { from: self.status, to });
+        };
+        self.require_role(caller, role)
+    }
+
+    /// Move to `to` on behalf of `caller`. Returns the status left.
+    pub(crate) fn transition(&mut self, caller: &str, to: DocStatus) -> Result<DocStatus, AppError> {
+        self.check_transition(caller, to)?;
+        let from = self.status;
+        self.status = to;
+        if to == DocStatus::Archived {
+            self.published = None;
+            self.expires_ms = None;
+        }
+        // a withdrawn or archived draft must not go out on schedule
+        if matches!(to, DocStatus::Draft | DocStatus::Archived) {
+            self.pending = None;
+            self.pending_expires_ms = None;
+        }
+        Ok(from)
+    }
+
+    fn snapshot(&self, caller: &str, at: u64) -> Snapshot {
+        Snapshot { content: self.content.clone(), version: self.version, published_ms: at, published_by: caller.to_string() }
+    }
+
+    /// Freeze the current text as the published snapshot, replacing any
+    /// scheduled one.
+    pub(crate) fn take_snapshot(&mut self, caller: &str, now: u64) {
+        self.published = Some(self.snapshot(caller, now));
+        self.expires_ms = None;
+        self.pending = None;
+        self.pending_expires_ms = None;
+    }
+
+    /// Freeze the current text now, to be shown from `publish_at` until
+    /// `expires_at`.
+    pub(crate) fn schedule_snapshot(&mut self, caller: &str, publish_at: u64, expires_at: Option<u64>) -> Result<(), AppError> {
+        if let Some(expires_ms) = expires_at.filter(|e| *e <= publish_at) {
+            return Err(AppError::ExpiryBeforePublish { publish_ms: publish_at, expires_ms });
+        }
+        self.check_transition(caller, DocStatus::Published)?;
+        self.pending = Some(self.snapshot(caller, publish_at));
+        self.pending_expires_ms = expires_at;
+        Ok(())
+    }
+
+    /// Set when the scheduled snapshot, or else the published one, stops
+    /// being shown.
+    pub(crate) fn set_expiry(&mut self, caller: &str, expires_at: Option<u64>) -> Result<(), AppError> {
+        self.require_role(caller, Role::Reviewer)?;
+        let (shown_from, expiry) = match (&self.pending, &self.published) {
+            (Some(pending), _) => (pending.published_ms, &mut self.pending_expires_ms),
+            (None, Some(published)) => (published.published_ms, &mut self.expires_ms),
+            (None, None) => return Err(AppError::NotPublished { document_id: self.id.clone() }),
+        };
+        if let Some(expires_ms) = expires_at.filter(|e| *e <= shown_from) {
+            return Err(AppError::ExpiryBeforePublish { publish_ms: shown_from, expires_ms });
+        }
+        *expiry = expires_at;
+        Ok(())
+    }
+
+    /// Apply a scheduled publish or expiry that is due at `now`, in order.
+    pub(crate) fn advance_schedule(&mut self, now: u64) -> Vec<Scheduled> {
+        let mut changes = Vec::new();
+        if self.pending.as_ref().is_some_and(|p| p.published_ms <= now) {
+            let from = self.status;
+            self.published = self.pending.take();
+            self.expires_ms = self.pending_expires_ms.take();
+            self.status = DocStatus::Published;
+            changes.extend(self.published.clone().map(|snapshot| Scheduled::Published { from, snapshot }));
+        }
+        if self.expires_ms.is_some_and(|e| e <= now) {
+            self.expires_ms = None;
+            let archived = self.status == DocStatus::Published;
+            if archived {
+                self.status = DocStatus::Archived;
+            }
+            if let Some(expired) = self.published.take() {
+                changes.push(Scheduled::Expired { version: expired.version, by: expired.published_by, archived });
+            }
+        }
+        changes
+    }
+
+    /// Snapshot readers see at `now`, whether or not due schedules have been
+    /// applied yet.
+    fn shown_at(&self, now: u64) -> Option<&Snapshot> {
+        let (snapshot, expires) = match &self.pending {
+            Some(pending) if pending.published_ms <= now => (Some(pending), self.pending_expires_ms),
+            _ => (self.published.as_ref(), self.expires_ms),
+        };
+        match expires {
+            Some(e) if e <= now => None,
+            _ => snapshot,
+        }
+    }
+
+    /// Status at `now`, counting schedules that are due.
+    pub(crate) fn status_at(&self, now: u64) -> DocStatus {
+        let (status, expires) = match &self.pending {
+            Some(pending) if pending.published_ms <= now => (DocStatus::Published, self.pending_expires_ms),
+            _ => (self.status, self.expires_ms),
+        };
+        match expires {
+            Some(e) if e <= now && status == DocStatus::Published => DocStatus::Archived,
+            _ => status,
+        }
+    }
+
+    pub(crate) fn published_view(&self, now: u64) -> Option<PublishedView> {
+        self.shown_at(now).map(|s| PublishedView {
+            document_id: self.id.clone(),
+            content: s.content.clone(),
+            version: s.version,
+            published_ms: s.published_ms,
+            published_by: s.published_by.clone(),
+        })
+    }
+}
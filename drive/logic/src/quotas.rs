@@ -0,0 +1,150 @@
+//! Size limits for the replicated state of a context.
+//!
+//! Every node stores everything any client writes, so one careless client
+//! could grow the state for all of them. The limits are part of the state
+//! and the owner of the context changes them for all of its documents.
+//! Lowering a limit does not touch what is already stored: a document above
+//! the new length limit still accepts edits that do not make it longer.
+//!
+//! Bookkeeping that grows as a side effect of other calls (notifications,
+//! remembered client batches and the audit log) is never a reason to reject
+//! a call. Past their limit the oldest entries are dropped instead.
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+
+use crate::crdt::DocOp;
+use crate::AppError;
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct Quotas {
+    /// Characters a document may hold
+    pub max_doc_len: u64,
+    /// Ops in one `apply_ops` batch
+    pub max_ops_per_call: u64,
+    /// Characters one insert or replace may add
+    pub max_insert_len: u64,
+    /// Documents in the context, templates included
+    pub max_documents: u64,
+    /// Executors tracked by `ping` at once
+    pub max_presence_entries: u64,
+    /// Bytes of a `ping` payload
+    pub max_payload_bytes: u64,
+    /// Bytes stored by all blobs together, counting shared chunks once
+    pub max_blob_bytes: u64,
+    /// Notifications kept per recipient
+    pub max_notifications: u64,
+    /// Client ids per editor and document whose batches are remembered
+    pub max_batch_clients: u64,
+    /// Entries kept in the audit log
+    pub max_audit_entries: u64,
+}
+
+impl Default for Quotas {
+    fn default() -> Self {
+        Quotas {
+            max_doc_len: 1_000_000,
+            max_ops_per_call: 1_000,
+            max_insert_len: 100_000,
+            max_documents: 1_000,
+            max_presence_entries: 1_000,
+            max_payload_bytes: 1_024,
+            max_blob_bytes: 64 * 1024 * 1024,
+            max_notifications: 1_000,
+            max_batch_clients: 64,
+            max_audit_entries: 10_000,
+        }
+    }
+}
+
+/// Fail with `Quota` when `value` is above `limit`.
+fn check(quota: &'static str, value: usize, limit: u64) -> Result<(), AppError> {
+    if value as u64 > limit {
+        return Err(AppError::Quota { quota, limit });
+    }
+    Ok(())
+}
+
+impl Quotas {
+    pub(crate) fn validate(&self) -> Result<(), AppError> {
+        let limits = [
+            ("max_doc_len", self.max_doc_len),
+            ("max_ops_per_call", self.max_ops_per_call),
+            ("max_insert_len", self.max_insert_len),
+            ("max_documents", self.max_documents),
+            ("max_presence_entries", self.max_presence_entries),
+            ("max_payload_bytes", self.max_payload_bytes),
+            ("max_blob_bytes", self.max_blob_bytes),
+            ("max_notifications", self.max_notifications),
+            ("max_batch_clients", self.max_batch_clients),
+            ("max_audit_entries", self.max_audit_entries),
+        ];
+        match limits.iter().find(|(_, limit)| *limit == 0) {
+            Some((quota, _)) => Err(AppError::InvalidQuota { quota }),
+            None => Ok(()),
+        }
+    }
+
+    /// Check the size of a batch and of each text it inserts.
+    pub(crate) fn check_ops(&self, ops: &[DocOp]) -> Result<(), AppError> {
+        check("max_ops_per_call", ops.len(), self.max_ops_per_call)?;
+        for op in ops {
+            if let DocOp::Insert { content, .. } | DocOp::Replace { content, .. } = op {
+                check("max_insert_len", content.chars().count(), self.max_insert_len)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the length `validated` ops leave a document of `len`
+    /// characters with. Edits that do not grow the document always pass.
+    pub(crate) fn check_edit(&self, len: usize, validated: &[DocOp]) -> Result<(), AppError> {
+        let mut after = len;
+        for op in validated {
+            match op {
+                DocOp::Insert { content, .. } => after += content.chars().count(),
+                DocOp::Delete { len, .. } => after -= len,
+                DocOp::Replace { len, content, .. } => after = after - len + content.chars().count(),
+                DocOp::Move { .. } => {}
+            }
+        }
+        self.check_growth(len, after)
+    }
+
+    /// Check a document going from `before` to `after` characters.
+    pub(crate) fn check_growth(&self, before: usize, after: usize) -> Result<(), AppError> {
+        if after > before {
+            check("max_doc_len", after, self.max_doc_len)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_content(&self, content: &str) -> Result<(), AppError> {
+        check("max_doc_len", content.chars().count(), self.max_doc_len)
+    }
+
+    /// Check room for one more document besides the `documents` there are.
+    pub(crate) fn check_documents(&self, documents: usize) -> Result<(), AppError> {
+        check("max_documents", documents + 1, self.max_documents)
+    }
+
+    /// Check a presence ping; `known` says whether its address already has
+    /// an entry among the `entries` tracked.
+    pub(crate) fn check_ping(&self, payload: &str, known: bool, entries: usize) -> Result<(), AppError> {
+        check("max_payload_bytes", payload.len(), self.max_payload_bytes)?;
+        if !known {
+            check("max_presence_entries", entries + 1, self.max_presence_entries)?;
+        }
+        Ok(())
+    }
+
+    /// Check the bytes all blobs would store after a `put_blob`.
+    pub(crate) fn check_blob_bytes(&self, stored: u64) -> Result<(), AppError> {
+        if stored > self.max_blob_bytes {
+            return Err(AppError::Quota { quota: "max_blob_bytes", limit: self.max_blob_bytes });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+//! Full-text search across the drive's documents.
+//!
+//! Text is split into runs of alphanumeric characters and case folded. The
+//! inverted index maps every folded term to the ids of the documents that
+//! contain it and lives in state next to the documents. When a document's
+//! content changes only the terms that appeared in or disappeared from that
+//! document are touched. A query looks each of its terms up as a prefix in
+//! the sorted term map, intersects the documents found and scans only those
+//! for match offsets and a snippet.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+
+use crate::SharedDocument;
+
+/// Characters of context kept on each side of the first match in a snippet.
+const SNIPPET_CONTEXT: usize = 30;
+
+/// A match in a document's content, in scalar positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct SearchMatch {
+    pub index: usize,
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct SearchHit {
+    pub document_id: String,
+    /// Text around the first match, with `…` where it was cut
+    pub snippet: String,
+    /// Scalar position in the content where `snippet` starts (before any `…`)
+    pub snippet_start: usize,
+    /// Every matching word, in document order
+    pub matches: Vec<SearchMatch>,
+}
+
+#[derive(Debug, Clone, Default, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+pub(crate) struct SearchIndex {
+    /// Folded term to the ids of the documents containing it, sorted
+    terms: BTreeMap<String, Vec<String>>,
+}
+
+struct Token {
+    index: usize,
+    len: usize,
+    folded: String,
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    for (i, ch) in text.chars().enumerate() {
+        if ch.is_alphanumeric() {
+            let token = current.get_or_insert_with(|| Token { index: i, len: 0, folded: String::new() });
+            token.len += 1;
+            token.folded.extend(ch.to_lowercase());
+        } else if let Some(token) = current.take() {
+            tokens.push(token);
+        }
+    }
+    tokens.extend(current);
+    tokens
+}
+
+fn terms(text: &str) -> BTreeSet<String> {
+    tokenize(text).into_iter().map(|t| t.folded).collect()
+}
+
+impl SearchIndex {
+    /// Reflect a change of `document_id`'s content from `old` to `new`.
+    pub(crate) fn update(&mut self, document_id: &str, old: &str, new: &str) {
+        let (old, new) = (terms(old), terms(new));
+        for term in old.difference(&new) {
+            if let Some(ids) = self.terms.get_mut(term) {
+                ids.retain(|id| id != document_id);
+                if ids.is_empty() {
+                    self.terms.remove(term);
+                }
+            }
+        }
+        for term in new.difference(&old) {
+            let ids = self.terms.entry(term.clone()).or_default();
+            if let Err(pos) = ids.binary_search_by(|id| id.as_str().cmp(document_id)) {
+                ids.insert(pos, document_id.to_string());
+            }
+        }
+    }
+
+    /// Documents containing a term that starts with `prefix`.
+    fn documents_with_prefix(&self, prefix: &str) -> BTreeSet<&str> {
+        self.terms
+            .range(prefix.to_string()..)
+            .take_while(|(term, _)| term.starts_with(prefix))
+            .flat_map(|(_, ids)| ids.iter().map(String::as_str))
+            .collect()
+    }
+}
+
+impl SharedDocument {
+    /// Documents containing every term of `query` as a word prefix, most
+    /// matches first.
+    pub(crate) fn run_search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let wanted: Vec<String> = tokenize(query).into_iter().map(|t| t.folded).collect();
+        let mut candidates: Option<BTreeSet<&str>> = None;
+        for term in &wanted {
+            let found = self.search_index.documents_with_prefix(term);
+            candidates = Some(match candidates {
+                None => found,
+                Some(c) => c.intersection(&found).copied().collect(),
+            });
+        }
+
+        let mut hits: Vec<SearchHit> = candidates
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|id| self.documents.iter().find(|d| d.id == id))
+            .filter_map(|doc| {
+                let matches: Vec<SearchMatch> = tokenize(&doc.content)
+                    .into_iter()
+                    .filter(|t| wanted.iter().any(|w| t.folded.starts_with(w.as_str())))
+                    .map(|t| SearchMatch { index: t.index, len: t.len })
+                    .collect();
+                let first = *matches.first()?;
+                let (snippet, snippet_start) = snippet(&doc.content, first);
+                Some(SearchHit { document_id: doc.id.clone(), snippet, snippet_start, matches })
+            })
+            .collect();
+        hits.sort_by(|a, b| b.matches.len().cmp(&a.matches.len()).then_with(|| a.document_id.cmp(&b.document_id)));
+        hits.truncate(limit);
+        hits
+    }
+}
+
+pub(crate) fn snippet(content: &str, around: SearchMatch) -> (String, usize) {
+    let len = content.chars().count();
+    let start = around.index.saturating_sub(SNIPPET_CONTEXT);
+    let end = (around.index + around.len + SNIPPET_CONTEXT).min(len);
+    let mut out = String::new();
+    if start > 0 {
+        out.push('…');
+    }
+    out.extend(content.chars().skip(start).take(end - start));
+    if end < len {
+        out.push('…');
+    }
+    (out, start)
+}
@@ -0,0 +1,349 @@
+//! Multi-replica convergence simulator.
+//!
+//! A scenario is a list of actions over N replicas: local inserts, deletes
+//! and range moves made through `apply_ops`, deliveries of pending messages
+//! picked out of order, duplicate deliveries and clock advances. Whatever is
+//! still in flight is delivered at the end, after which every replica must
+//! hold the same content and the same element sequence. Scenarios are
+//! generated from a seed and failing ones are shrunk to a minimal list of
+//! actions before being reported.
+//!
+//! Three transports are modelled. `RootState` is what the runtime does: every
+//! call persists the whole root state, and a replica adopts a state written
+//! after the one it holds, so the last writer wins. `ElementOps` ships the
+//! identity-based `SeqOp`s each edit produced; production never sends them,
+//! but they exercise the merge the sequence is designed for. `DocOpReplay`
+//! re-executes the index-based `DocOp` batch on every other replica, which is
+//! what naive action replay would do.
+//!
+//! Known failures are kept as ignored tests that reproduce them: root-state
+//! sync converges but drops concurrent edits, and index replay diverges.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::crdt::{HlcTimestamp, Integration, SeqOp};
+use crate::env::mock;
+use crate::testing::Replica;
+use crate::DocOp;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    RootState,
+    ElementOps,
+    DocOpReplay,
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    /// Insert `text` at `at % (len + 1)` on `replica`.
+    Insert { replica: usize, at: usize, text: String },
+    /// Delete up to `len` chars from `at % len` on `replica`, stopping at the end.
+    Delete { replica: usize, at: usize, len: usize },
+    /// Move up to `len` chars from `at % len` to `to % (len + 1)` on `replica`.
+    Move { replica: usize, at: usize, len: usize, to: usize },
+    /// Deliver and drop the pending message at `slot % pending` on `replica`.
+    Deliver { replica: usize, slot: usize },
+    /// Deliver the pending message at `slot % pending` on `replica` but keep it queued.
+    Duplicate { replica: usize, slot: usize },
+    Tick { ms: u64 },
+}
+
+/// When a root state was written and by whom; later writes win.
+type Stamp = (HlcTimestamp, [u8; 32]);
+
+#[derive(Clone)]
+enum Message {
+    State { stamp: Stamp, bytes: Vec<u8> },
+    Element(SeqOp),
+    Replay { executor: [u8; 32], ops: Vec<DocOp> },
+}
+
+struct Node {
+    replica: Replica,
+    inbox: Vec<Message>,
+    deferred: Vec<SeqOp>,
+    // write that produced the root state this node holds
+    stamp: Stamp,
+}
+
+/// Small deterministic PRNG (xorshift64*) so scenarios replay from a seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+}
+
+fn generate(seed: u64, replicas: usize, steps: usize) -> Vec<Action> {
+    let mut rng = Rng::new(seed);
+    (0..steps)
+        .map(|_| {
+            let replica = rng.below(replicas);
+            match rng.below(10) {
+                0..=2 => {
+                    let text = (0..1 + rng.below(3)).map(|_| (b'a' + rng.below(26) as u8) as char).collect();
+                    Action::Insert { replica, at: rng.below(64), text }
+                }
+                3 => Action::Delete { replica, at: rng.below(64), len: 1 + rng.below(3) },
+                4 => Action::Move { replica, at: rng.below(64), len: 1 + rng.below(3), to: rng.below(64) },
+                5..=7 => Action::Deliver { replica, slot: rng.below(64) },
+                8 => Action::Duplicate { replica, slot: rng.below(64) },
+                _ => Action::Tick { ms: rng.below(50) as u64 },
+            }
+        })
+        .collect()
+}
+
+fn snapshot(replica: &Replica) -> HashMap<u128, bool> {
+    replica.main().elems.iter().map(|e| (e.uid, e.visible)).collect()
+}
+
+/// Element ops a local call produced, recovered by diffing the sequence.
+/// Inserts come out in sequence order so origins precede their children.
+/// Slots hidden only because their item moved need no op of their own.
+fn produced_ops(before: &HashMap<u128, bool>, replica: &Replica) -> Vec<SeqOp> {
+    let shown: HashSet<u128> = replica.main().elems.iter().filter(|e| e.visible).map(|e| e.item).collect();
+    let mut ops = Vec::new();
+    for e in &replica.main().elems {
+        match before.get(&e.uid) {
+            None => ops.push(SeqOp::Insert { elem: e.clone() }),
+            Some(true) if !e.visible && !shown.contains(&e.item) => ops.push(SeqOp::Remove { uid: e.uid }),
+            _ => {}
+        }
+    }
+    ops
+}
+
+impl Node {
+    fn receive(&mut self, message: Message) {
+        match message {
+            Message::State { stamp, bytes } => {
+                if stamp > self.stamp {
+                    self.replica.load(&bytes);
+                    self.stamp = stamp;
+                }
+            }
+            Message::Element(op) => {
+                if self.replica.integrate(op.clone()) == Integration::MissingDependency {
+                    self.deferred.push(op);
+                } else {
+                    self.retry_deferred();
+                }
+                self.replica.main_mut().rebuild_content();
+            }
+            Message::Replay { executor, ops } => {
+                mock::set_executor(executor);
+                let _ = self.replica.doc.apply_ops(ops, None, None, None);
+            }
+        }
+    }
+
+    fn retry_deferred(&mut self) {
+        loop {
+            let pending = std::mem::take(&mut self.deferred);
+            let before = pending.len();
+            for op in pending {
+                if self.replica.integrate(op.clone()) == Integration::MissingDependency {
+                    self.deferred.push(op);
+                }
+            }
+            if self.deferred.len() == before {
+                break;
+            }
+        }
+    }
+}
+
+/// Run a scenario and describe the divergence, if any. With `keep_edits`,
+/// every element minted anywhere must also survive on the replicas.
+fn run(actions: &[Action], replicas: usize, transport: Transport, keep_edits: bool) -> Result<(), String> {
+    mock::reset();
+    mock::set_time(1_000);
+
+    let mut nodes: Vec<Node> = (0..replicas)
+        .map(|i| {
+            let skew = (i as i64 - 1) * 5_000;
+            let replica = Replica::new(i as u8 + 1).with_skew(skew);
+            Node { replica, inbox: Vec::new(), deferred: Vec::new(), stamp: (HlcTimestamp::default(), [0; 32]) }
+        })
+        .collect();
+    let mut minted = HashSet::new();
+
+    for action in actions {
+        match action {
+            Action::Insert { replica, .. } | Action::Delete { replica, .. } | Action::Move { replica, .. } => {
+                let node = &mut nodes[*replica];
+                let len = node.replica.main().content.chars().count();
+                let op = match action {
+                    Action::Insert { at, text, .. } => DocOp::Insert { index: at % (len + 1), content: text.clone() },
+                    Action::Delete { at, len: del, .. } if len > 0 => DocOp::Delete { index: at % len, len: (*del).min(len - at % len) },
+                    Action::Move { at, len: n, to, .. } if len > 0 => {
+                        let (from, n, to) = (at % len, (*n).min(len - at % len), to % (len + 1));
+                        if to > from && to < from + n {
+                            continue;
+                        }
+                        DocOp::Move { from, len: n, to }
+                    }
+                    _ => continue,
+                };
+                let before = snapshot(&node.replica);
+                if node.replica.call(|doc| doc.apply_ops(vec![op.clone()], None, None, None)).is_err() {
+                    continue;
+                }
+                minted.extend(node.replica.main().elems.iter().map(|e| e.uid).filter(|uid| !before.contains_key(uid)));
+                let messages = match transport {
+                    Transport::RootState => {
+                        node.stamp = (node.replica.doc.clock, node.replica.executor);
+                        vec![Message::State { stamp: node.stamp, bytes: node.replica.persisted() }]
+                    }
+                    Transport::ElementOps => produced_ops(&before, &node.replica).into_iter().map(Message::Element).collect(),
+                    Transport::DocOpReplay => vec![Message::Replay { executor: node.replica.executor, ops: vec![op] }],
+                };
+                for (i, other) in nodes.iter_mut().enumerate() {
+                    if i != *replica {
+                        other.inbox.extend(messages.iter().cloned());
+                    }
+                }
+            }
+            Action::Deliver { replica, slot } | Action::Duplicate { replica, slot } => {
+                let node = &mut nodes[*replica];
+                if node.inbox.is_empty() {
+                    continue;
+                }
+                let slot = slot % node.inbox.len();
+                let message = match action {
+                    Action::Deliver { .. } => node.inbox.remove(slot),
+                    _ => node.inbox[slot].clone(),
+                };
+                node.receive(message);
+            }
+            Action::Tick { ms } => mock::advance_time(*ms),
+        }
+    }
+
+    for node in nodes.iter_mut() {
+        for message in std::mem::take(&mut node.inbox) {
+            node.receive(message);
+        }
+        if !node.deferred.is_empty() {
+            return Err(format!("{} ops never became applicable", node.deferred.len()));
+        }
+    }
+
+    let reference = nodes[0].replica.main();
+    for (i, node) in nodes.iter().enumerate().skip(1) {
+        let doc = node.replica.main();
+        if doc.content != reference.content {
+            return Err(format!("replica {i} content {:?} != replica 0 content {:?}", doc.content, reference.content));
+        }
+        let seq = |d: &crate::document::Document| d.elems.iter().map(|e| (e.uid, e.visible)).collect::<Vec<_>>();
+        if seq(doc) != seq(reference) {
+            return Err(format!("replica {i} element sequence differs from replica 0"));
+        }
+    }
+    if keep_edits {
+        let lost = minted.iter().filter(|uid| !reference.elems.iter().any(|e| e.uid == **uid)).count();
+        if lost > 0 {
+            return Err(format!("{lost} of {} minted elements were dropped", minted.len()));
+        }
+    }
+    Ok(())
+}
+
+/// Delta-debugging shrink: drop ever smaller chunks of actions while the
+/// scenario keeps failing.
+fn shrink(mut actions: Vec<Action>, fails: impl Fn(&[Action]) -> bool) -> Vec<Action> {
+    let mut chunk = actions.len() / 2;
+    while chunk > 0 {
+        let mut progressed = false;
+        let mut start = 0;
+        while start < actions.len() {
+            let end = (start + chunk).min(actions.len());
+            let mut candidate = actions.clone();
+            candidate.drain(start..end);
+            if fails(&candidate) {
+                actions = candidate;
+                progressed = true;
+            } else {
+                start = end;
+            }
+        }
+        if !progressed {
+            chunk /= 2;
+        }
+    }
+    actions
+}
+
+/// Run `seeds` random scenarios, returning the shrunk failures.
+fn fuzz(seeds: u64, replicas: usize, steps: usize, check: impl Fn(&[Action]) -> Result<(), String>) -> Vec<(u64, Vec<Action>, String)> {
+    let mut failures = Vec::new();
+    for seed in 0..seeds {
+        let actions = generate(seed, replicas, steps);
+        if let Err(reason) = check(&actions) {
+            let minimal = shrink(actions, |a| check(a).is_err());
+            failures.push((seed, minimal, reason));
+        }
+    }
+    failures
+}
+
+fn assert_no_failures(seeds: u64, replicas: usize, steps: usize, transport: Transport, keep_edits: bool) {
+    let failures = fuzz(seeds, replicas, steps, |a| run(a, replicas, transport, keep_edits));
+    if let Some((seed, minimal, reason)) = failures.first() {
+        panic!(
+            "{} of {seeds} scenarios with {replicas} replicas failed over {transport:?}; seed {seed}: {reason}\nminimal reproduction: {minimal:#?}",
+            failures.len()
+        );
+    }
+}
+
+#[test]
+fn root_state_sync_converges_under_reordering_and_duplication() {
+    for replicas in 2..=4 {
+        assert_no_failures(150, replicas, 60, Transport::RootState, false);
+    }
+}
+
+#[test]
+#[ignore = "known failure: last-writer-wins root sync drops concurrent edits"]
+fn root_state_sync_keeps_concurrent_edits() {
+    assert_no_failures(50, 3, 60, Transport::RootState, true);
+}
+
+#[test]
+fn element_ops_converge_under_reordering_and_duplication() {
+    for replicas in 2..=4 {
+        assert_no_failures(150, replicas, 60, Transport::ElementOps, true);
+    }
+}
+
+#[test]
+#[ignore = "known failure: index-based ops resolve differently on replicas that saw other edits"]
+fn index_replay_converges() {
+    assert_no_failures(50, 3, 60, Transport::DocOpReplay, false);
+}
+
+#[test]
+fn shrink_reduces_to_the_failing_core() {
+    let actions = generate(7, 3, 40);
+    let fails = |a: &[Action]| {
+        a.iter().any(|x| matches!(x, Action::Delete { .. })) && a.iter().any(|x| matches!(x, Action::Duplicate { .. }))
+    };
+    assert!(fails(&actions));
+
+    let minimal = shrink(actions, fails);
+    assert_eq!(minimal.len(), 2);
+}
@@ -0,0 +1,130 @@
+//! Document templates and placeholder expansion.
+//!
+//! A template is an ordinary document registered under a display name, so
+//! it is edited, leased and searched like any other. Instantiating one copies
+//! its current text into a new document, replacing `{{name}}` placeholders
+//! with the caller's variables. `{{author}}` and `{{date}}` are filled in by
+//! default with the caller and the current UTC date. Placeholders without a
+//! value are left as they are.
+
+use calimero_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use calimero_sdk::serde::{Deserialize, Serialize};
+
+use crate::{AppError, SharedDocument};
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct TemplateVar {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+#[serde(crate = "calimero_sdk::serde")]
+pub struct TemplateView {
+    pub template_id: String,
+    pub name: String,
+    /// Placeholder names used in the template, in order of first use
+    pub variables: Vec<String>,
+}
+
+/// Registration of a document as a template.
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "calimero_sdk::borsh")]
+pub(crate) struct Template {
+    pub(crate) document_id: String,
+    pub(crate) name: String,
+}
+
+/// Placeholders in `text` as `(byte start, byte end, name)`.
+fn placeholders(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut out = Vec::new();
+    let mut from = 0;
+    while let Some(open) = text[from..].find("{{").map(|i| from + i) {
+        let Some(close) = text[open + 2..].find("}}").map(|i| open + 2 + i) else {
+            break;
+        };
+        let name = text[open + 2..close].trim();
+        if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            out.push((open, close + 2, name));
+            from = close + 2;
+        } else {
+            from = open + 1;
+        }
+    }
+    out
+}
+
+pub(crate) fn variables(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (_, _, name) in placeholders(text) {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Replace every placeholder that has a value in `vars`.
+pub(crate) fn fill(text: &str, vars: &[TemplateVar]) -> String {
+    let mut out = String::new();
+    let mut copied = 0;
+    for (start, end, name) in placeholders(text) {
+        if let Some(var) = vars.iter().find(|v| v.name == name) {
+            out.push_str(&text[copied..start]);
+            out.push_str(&var.value);
+            copied = end;
+        }
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+/// `YYYY-MM-DD` of a millisecond Unix timestamp, in UTC.
+pub(crate) fn iso_date(ms: u64) -> String {
+    // days to civil date, after Howard Hinnant's `civil_from_days`
+    let z = (ms / 86_400_000) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+impl SharedDocument {
+    pub(crate) fn template(&self, template_id: &str) -> Result<&Template, AppError> {
+        self.templates
+            .iter()
+            .find(|t| t.document_id == template_id)
+            .ok_or_else(|| AppError::NotFound { kind: "template", id: template_id.to_string() })
+    }
+
+    pub(crate) fn template_views(&self) -> Vec<TemplateView> {
+        self.templates
+            .iter()
+            .filter_map(|t| {
+                let doc = self.document(Some(&t.document_id)).ok()?;
+                Some(TemplateView { template_id: t.document_id.clone(), name: t.name.clone(), variables: variables(&doc.content) })
+            })
+            .collect()
+    }
+
+    /// `requested`, or the first unused `<base>-<n>` when none was given.
+    pub(crate) fn new_document_id(&self, requested: Option<String>, base: &str) -> Result<String, AppError> {
+        match requested {
+            Some(id) if id.is_empty() => Err(AppError::Empty { field: "document_id" }),
+            Some(id) if self.document_index(Some(&id)).is_ok() => Err(AppError::Conflict { kind: "document", id }),
+            Some(id) => Ok(id),
+            None => Ok((1..)
+                .map(|n| format!("{base}-{n}"))
+                .find(|id| self.document_index(Some(id)).is_err())
+                .expect("unbounded ids")),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Off-chain harness: simulated replicas driven through the mock environment.
+
+use calimero_sdk::borsh;
+
+use crate::crdt::{uid_timestamp, Integration, SeqOp};
+use crate::document::Document;
+use crate::env::mock;
+use crate::SharedDocument;
+
+/// One node of a simulated context: an executor identity, a wall clock skew
+/// relative to the mock clock and its own copy of the state.
+pub(crate) struct Replica {
+    pub(crate) executor: [u8; 32],
+    pub(crate) skew_ms: i64,
+    pub(crate) doc: SharedDocument,
+}
+
+impl Replica {
+    pub(crate) fn new(seed: u8) -> Self {
+        // `init` runs as the replica's executor, who becomes the context owner
+        mock::set_executor([seed; 32]);
+        Replica { executor: [seed; 32], skew_ms: 0, doc: SharedDocument::init() }
+    }
+
+    pub(crate) fn with_skew(mut self, skew_ms: i64) -> Self {
+        self.skew_ms = skew_ms;
+        self
+    }
+
+    pub(crate) fn address(&self) -> String {
+        mock::address_of(self.executor)
+    }
+
+    /// The default document of this replica's state.
+    pub(crate) fn main(&self) -> &Document {
+        &self.doc.documents[0]
+    }
+
+    pub(crate) fn main_mut(&mut self) -> &mut Document {
+        &mut self.doc.documents[0]
+    }
+
+    /// Run `f` against this replica's state as its executor, with the mock
+    /// clock shifted by the replica's skew for the duration of the call.
+    pub(crate) fn call<T>(&mut self, f: impl FnOnce(&mut SharedDocument) -> T) -> T {
+        let base = crate::env::time_now();
+        mock::set_executor(self.executor);
+        mock::set_time(base.saturating_add_signed(self.skew_ms));
+        let out = f(&mut self.doc);
+        mock::set_time(base);
+        out
+    }
+
+    /// Integrate an element op shipped from another replica into the default
+    /// document, merging the timestamp of an inserted element into the clock.
+    pub(crate) fn integrate(&mut self, op: SeqOp) -> Integration {
+        if let SeqOp::Insert { elem } = &op {
+            self.doc.clock.observe(uid_timestamp(elem.uid));
+        }
+        self.main_mut().integrate(op)
+    }
+
+    /// Replace this replica's state with `other`'s persisted state, as a
+    /// context sync of the root state would.
+    pub(crate) fn sync_from(&mut self, other: &Replica) {
+        self.load(&other.persisted());
+    }
+
+    /// This replica's state as the runtime persists it.
+    pub(crate) fn persisted(&self) -> Vec<u8> {
+        borsh::to_vec(&self.doc).expect("serialize state")
+    }
+
+    pub(crate) fn load(&mut self, bytes: &[u8]) {
+        self.doc = borsh::from_slice(bytes).expect("deserialize state");
+    }
+}
@@ -0,0 +1,92 @@
+//! Conversion of caller offsets between index units.
+//!
+//! The document is stored as Unicode scalar values, but browser editors count
+//! UTF-16 code units and users think in grapheme clusters. Offsets in either
+//! unit are resolved to scalar positions here, against the text the op will
+//! actually see.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::batches::IndexUnit;
+
+/// Where a caller offset lands in the text, in scalar positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Offset {
+    At(usize),
+    /// Inside a surrogate pair or grapheme cluster spanning `start..end`
+    Inside { start: usize, end: usize },
+    PastEnd,
+}
+
+/// Scalar positions of every grapheme cluster boundary, including 0 and the end.
+fn grapheme_boundaries(text: &[char]) -> Vec<usize> {
+    let s: String = text.iter().collect();
+    let mut bounds = vec![0];
+    let mut pos = 0;
+    for g in s.graphemes(true) {
+        pos += g.chars().count();
+        bounds.push(pos);
+    }
+    bounds
+}
+
+/// Length of `text` counted in `unit`.
+pub(crate) fn unit_len(text: &[char], unit: IndexUnit) -> usize {
+    match unit {
+        IndexUnit::Scalar => text.len(),
+        IndexUnit::Utf16 => text.iter().map(|c| c.len_utf16()).sum(),
+        IndexUnit::Grapheme => grapheme_boundaries(text).len() - 1,
+    }
+}
+
+/// Resolve `offset` counted in `unit` to a scalar position in `text`. With
+/// `whole_graphemes`, scalar and UTF-16 offsets must also fall on a grapheme
+/// boundary.
+pub(crate) fn resolve(text: &[char], offset: usize, unit: IndexUnit, whole_graphemes: bool) -> Offset {
+    let pos = match unit {
+        IndexUnit::Scalar if offset <= text.len() => offset,
+        IndexUnit::Scalar => return Offset::PastEnd,
+        IndexUnit::Grapheme => {
+            return match grapheme_boundaries(text).get(offset) {
+                Some(pos) => Offset::At(*pos),
+                None => Offset::PastEnd,
+            };
+        }
+        IndexUnit::Utf16 => {
+            let mut units = 0;
+            let mut found = None;
+            for (i, c) in text.iter().enumerate() {
+                if units == offset {
+                    found = Some(i);
+                    break;
+                }
+                units += c.len_utf16();
+                if units > offset {
+                    if whole_graphemes {
+                        let (start, end) = cluster_around(text, i);
+                        return Offset::Inside { start, end };
+                    }
+                    return Offset::Inside { start: i, end: i + 1 };
+                }
+            }
+            match found {
+                Some(pos) => pos,
+                None if units == offset => text.len(),
+                None => return Offset::PastEnd,
+            }
+        }
+    };
+
+    if whole_graphemes && grapheme_boundaries(text).binary_search(&pos).is_err() {
+        let (start, end) = cluster_around(text, pos);
+        return Offset::Inside { start, end };
+    }
+    Offset::At(pos)
+}
+
+/// Scalar range of the grapheme cluster containing the scalar at `pos`.
+fn cluster_around(text: &[char], pos: usize) -> (usize, usize) {
+    let bounds = grapheme_boundaries(text);
+    let next = bounds.partition_point(|&b| b <= pos);
+    (bounds[next - 1], bounds[next])
+}